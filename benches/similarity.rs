@@ -0,0 +1,101 @@
+// Compares the plain iterator similarity pass against FlatIndex on the 72 float sword state.
+// Run with `cargo bench --bench similarity`.
+
+use std::{hint::black_box, time::Instant};
+
+use blended_markov_distribution::{
+    bmd::{BlendedDist, Interpolate, PositionState, WeightedSpikes, BMD},
+    data,
+    flat::FlatIndex,
+    lookback::{FromHistory, Lookback},
+};
+
+const ROUNDS: usize = 200;
+
+fn time<F: FnMut()>(name: &str, mut f: F) {
+    // warm up
+    for _ in 0..10 {
+        f();
+    }
+
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        f();
+    }
+    let per_round = start.elapsed() / ROUNDS as u32;
+
+    println!("{name:<40} {per_round:?}");
+}
+
+fn main() {
+    let dif_data: Vec<[f32; 6]> = data::sword_6()
+        .windows(2)
+        .map(|win| {
+            let mut buf = [0.0; 6];
+            for i in 0..6 {
+                buf[i] = win[1][i] - win[0][i];
+            }
+            buf
+        })
+        .collect();
+
+    // repeat the clip with a little offset each time so the model is big enough to matter
+    let mut series = Vec::new();
+    for copy in 0..200 {
+        series.extend(dif_data.iter().map(|f| f.map(|x| x + copy as f32 * 1e-4)));
+    }
+
+    let bmd: BMD<Lookback<12, 6>, _> = BMD::from_series(&series, 0.0);
+    let query = Lookback::<12, 6>::from_history(&dif_data[..12]);
+
+    println!("{} stored 72 float states", bmd.distributions.len());
+
+    time("iterator similarities", || {
+        let weights: Vec<f32> = bmd
+            .distributions
+            .iter()
+            .map(|(pos, _)| pos.similarity(&query))
+            .collect();
+        black_box(weights);
+    });
+
+    let mut serial = FlatIndex::new(&bmd);
+    serial.threads = 1;
+    time("flat similarities, 1 thread", || {
+        black_box(serial.similarities(&query));
+    });
+
+    let mut parallel = FlatIndex::new(&bmd);
+    parallel.parallel_above = 0;
+    time(
+        &format!("flat similarities, {} threads", parallel.threads),
+        || {
+            black_box(parallel.similarities(&query));
+        },
+    );
+
+    time("interpolate + sample", || {
+        black_box(bmd.interpolate::<WeightedSpikes<[f32; 6]>>(query).sample());
+    });
+
+    time("interpolate_with + sample", || {
+        black_box(
+            bmd.interpolate_with::<WeightedSpikes<[f32; 6]>>(&serial, query)
+                .sample(),
+        );
+    });
+
+    // make sure both ways agree before trusting the numbers
+    let expected: Vec<f32> = bmd
+        .distributions
+        .iter()
+        .map(|(pos, _)| pos.similarity(&query))
+        .collect();
+    let worst = serial
+        .similarities(&query)
+        .iter()
+        .zip(&expected)
+        .map(|(a, b)| (a - b).abs())
+        .fold(0.0_f32, f32::max);
+    println!("largest difference from the iterator version: {worst}");
+}
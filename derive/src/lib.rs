@@ -0,0 +1,124 @@
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Expr, Fields, Index, Path};
+
+// #[derive(PositionState)] for structs made of other states.
+//
+// Field similarities are combined as a weighted sum of log-similarities, configured per field with
+//     #[similarity(weight = 2.0)]              how much the field counts, 1.0 by default
+//     #[similarity(metric = my_similarity)]    fn(&T, &T) -> f32 to use instead of T's PositionState
+//     #[similarity(skip)]                      leave the field out entirely
+// Fields compared through PositionState get a where clause saying so, which covers generic fields.
+#[proc_macro_derive(PositionState, attributes(similarity))]
+pub fn derive_position_state(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    match expand(input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+struct FieldConfig {
+    weight: Option<Expr>,
+    metric: Option<Path>,
+    skip: bool,
+}
+
+fn field_config(field: &syn::Field) -> syn::Result<FieldConfig> {
+    let mut config = FieldConfig {
+        weight: None,
+        metric: None,
+        skip: false,
+    };
+
+    for attr in field.attrs.iter().filter(|a| a.path().is_ident("similarity")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("weight") {
+                config.weight = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident("metric") {
+                config.metric = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident("skip") {
+                config.skip = true;
+            } else {
+                return Err(meta.error("expected `weight`, `metric` or `skip`"));
+            }
+            Ok(())
+        })?;
+    }
+
+    Ok(config)
+}
+
+fn expand(input: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let fields = match &input.data {
+        Data::Struct(data) => &data.fields,
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "PositionState can only be derived for structs",
+            ))
+        }
+    };
+
+    let members: Vec<_> = match fields {
+        Fields::Named(named) => named
+            .named
+            .iter()
+            .map(|f| {
+                let ident = f.ident.as_ref().unwrap();
+                (f, quote!(#ident))
+            })
+            .collect(),
+        Fields::Unnamed(unnamed) => unnamed
+            .unnamed
+            .iter()
+            .enumerate()
+            .map(|(i, f)| {
+                let index = Index::from(i);
+                (f, quote!(#index))
+            })
+            .collect(),
+        Fields::Unit => Vec::new(),
+    };
+
+    let mut parts = Vec::new();
+    let mut bounds = Vec::new();
+    for (field, member) in members {
+        let config = field_config(field)?;
+        if config.skip {
+            continue;
+        }
+
+        let ty = &field.ty;
+        let weight = match config.weight {
+            Some(w) => quote!(#w),
+            None => quote!(1.0),
+        };
+        let metric = match config.metric {
+            Some(m) => quote!(#m),
+            None => {
+                bounds.push(quote!(#ty: ::blended_markov_distribution::bmd::PositionState));
+                quote!(<#ty as ::blended_markov_distribution::bmd::PositionState>::similarity)
+            }
+        };
+
+        parts.push(quote!((#weight, #metric(&self.#member, &other.#member))));
+    }
+
+    let name = &input.ident;
+    let mut generics = input.generics.clone();
+    let where_clause = generics.make_where_clause();
+    for bound in bounds {
+        where_clause.predicates.push(syn::parse2(bound)?);
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::blended_markov_distribution::bmd::PositionState for #name #ty_generics #where_clause {
+            fn similarity(&self, other: &Self) -> f32 {
+                ::blended_markov_distribution::composite::weighted_log_similarity([#(#parts),*])
+            }
+        }
+    })
+}
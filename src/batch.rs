@@ -0,0 +1,139 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Mutex,
+};
+
+use rand::{rngs::StdRng, SeedableRng};
+
+use crate::bmd::{BlendedDist, Interpolate};
+
+// splitmix64, so neighbouring rollout indices still get unrelated seeds
+pub fn rollout_seed(master: u64, index: u64) -> u64 {
+    let mut z = master.wrapping_add(index.wrapping_add(1).wrapping_mul(0x9e3779b97f4a7c15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+// Many independent rollouts spread over a pool of threads.
+// Rollout i always gets an rng seeded from `seed` and i, so the results are the same
+// no matter how many threads there are or which one picks up which rollout.
+#[derive(Debug, Clone, Copy)]
+pub struct Batch {
+    pub rollouts: usize,
+    pub seed: u64,
+    pub threads: usize,
+}
+
+impl Batch {
+    pub fn new(rollouts: usize, seed: u64) -> Self {
+        Batch {
+            rollouts,
+            seed,
+            threads: std::thread::available_parallelism().map_or(1, |n| n.get()),
+        }
+    }
+
+    // Calls `rollout` once per index and gives back the results in index order.
+    // Threads pull the next index as they finish, so uneven rollouts still balance out.
+    pub fn run<R, F>(&self, rollout: F) -> Vec<R>
+    where
+        F: Fn(usize, &mut StdRng) -> R + Sync,
+        R: Send,
+    {
+        let next = AtomicUsize::new(0);
+        let results = Mutex::new(Vec::with_capacity(self.rollouts));
+
+        std::thread::scope(|scope| {
+            for _ in 0..self.threads.clamp(1, self.rollouts.max(1)) {
+                scope.spawn(|| {
+                    let mut done = Vec::new();
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        if index >= self.rollouts {
+                            break;
+                        }
+
+                        let mut rng = StdRng::seed_from_u64(rollout_seed(self.seed, index as u64));
+                        done.push((index, rollout(index, &mut rng)));
+                    }
+                    results.lock().unwrap().extend(done);
+                });
+            }
+        });
+
+        let mut results = results.into_inner().unwrap();
+        results.sort_by_key(|(index, _)| *index);
+        results.into_iter().map(|(_, r)| r).collect()
+    }
+
+    // Rolls the model out `length` frames after `history` once per rollout, only the new frames are kept.
+    // `make_state` builds the position state from the frames so far, newest last.
+    pub fn generate<'a, M, T, Out, F>(
+        &self,
+        model: &'a M,
+        history: &[Out],
+        length: usize,
+        make_state: F,
+    ) -> Vec<Vec<Out>>
+    where
+        M: Interpolate + Sync,
+        M::Dist: 'a,
+        T: BlendedDist<'a, &'a M::Dist, OutputState = Out> + Default + 'a,
+        Out: Clone + Send + Sync,
+        F: Fn(&[Out]) -> M::Pos + Sync,
+    {
+        self.run(|_, rng| {
+            model.rollout::<T, _, _, _, _>(history, length, &make_state, &mut (), rng)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+
+    use super::*;
+    use crate::{
+        bmd::{RExp, WeightedCUDs, BMD},
+        distribution::CUD,
+    };
+
+    fn batch(threads: usize) -> Batch {
+        Batch {
+            rollouts: 37,
+            seed: 5,
+            threads,
+        }
+    }
+
+    #[test]
+    fn run_is_the_same_on_any_number_of_threads() {
+        let draws = |threads| batch(threads).run(|i, rng| (i, rng.gen::<u64>()));
+
+        let single = draws(1);
+        assert_eq!(single.len(), 37);
+        assert!(single.iter().enumerate().all(|(i, (index, _))| i == *index));
+        assert_eq!(single, draws(4));
+        assert_eq!(single, draws(64));
+    }
+
+    #[test]
+    fn generate_is_the_same_on_any_number_of_threads() {
+        let bmd = BMD {
+            distributions: (0..20)
+                .map(|i| {
+                    let x = i as f32 * 0.1;
+                    (RExp(x), CUD { a: x, b: x + 0.2 })
+                })
+                .collect(),
+        };
+        let rollouts = |threads| {
+            batch(threads).generate::<_, WeightedCUDs, _, _>(&bmd, &[0.5], 10, |frames| {
+                RExp(*frames.last().unwrap())
+            })
+        };
+
+        assert_eq!(rollouts(1), rollouts(3));
+    }
+}
@@ -0,0 +1,102 @@
+use crate::{
+    bmd::{BlendedDist, Interpolate},
+    lookback::FromHistory,
+};
+
+// Keeps the `width` best partial trajectories by total log density, trying `expansions`
+// candidates from each one's blended distribution every frame.
+#[derive(Debug, Clone, Copy)]
+pub struct BeamSearch {
+    pub width: usize,
+    pub expansions: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct Beam<const OUT: usize> {
+    pub frames: Vec<[f32; OUT]>,
+    pub log_density: f32,
+}
+
+impl BeamSearch {
+    pub fn new(width: usize, expansions: usize) -> Self {
+        BeamSearch { width, expansions }
+    }
+
+    // Searches `length` frames after `history`, only the new frames end up in the beams.
+    // Every surviving beam is returned, most likely first.
+    pub fn run<'a, M, T, const OUT: usize>(
+        &self,
+        model: &'a M,
+        history: &[[f32; OUT]],
+        length: usize,
+    ) -> Vec<Beam<OUT>>
+    where
+        M: Interpolate,
+        M::Pos: FromHistory<OUT>,
+        M::Dist: 'a,
+        T: BlendedDist<'a, &'a M::Dist, OutputState = [f32; OUT]> + 'a,
+    {
+        let mut beams = vec![Beam {
+            frames: history.to_vec(),
+            log_density: 0.0,
+        }];
+
+        for _ in 0..length {
+            let mut expanded = Vec::new();
+
+            for beam in &beams {
+                let dist = model.interpolate::<T>(M::Pos::from_history(&beam.frames));
+
+                for candidate in dist.candidates(self.expansions) {
+                    let density = dist.evalutate(candidate);
+                    if density <= 0.0 {
+                        continue;
+                    }
+
+                    expanded.push((beam, candidate, beam.log_density + density.ln()));
+                }
+            }
+
+            if expanded.is_empty() {
+                // nothing anywhere had any density, stop with what we have
+                break;
+            }
+
+            expanded.sort_by(|a, b| b.2.total_cmp(&a.2));
+            expanded.truncate(self.width);
+
+            beams = expanded
+                .into_iter()
+                .map(|(beam, candidate, log_density)| {
+                    let mut frames = beam.frames.clone();
+                    frames.push(candidate);
+                    Beam {
+                        frames,
+                        log_density,
+                    }
+                })
+                .collect();
+        }
+
+        for beam in &mut beams {
+            beam.frames.drain(..history.len());
+        }
+        beams
+    }
+
+    // the most likely rollout found
+    pub fn best<'a, M, T, const OUT: usize>(
+        &self,
+        model: &'a M,
+        history: &[[f32; OUT]],
+        length: usize,
+    ) -> Beam<OUT>
+    where
+        M: Interpolate,
+        M::Pos: FromHistory<OUT>,
+        M::Dist: 'a,
+        T: BlendedDist<'a, &'a M::Dist, OutputState = [f32; OUT]> + 'a,
+    {
+        self.run::<M, T, OUT>(model, history, length).swap_remove(0)
+    }
+}
@@ -1,8 +1,13 @@
-use rand::{
-    distributions::WeightedIndex, prelude::Distribution, random, seq::SliceRandom, thread_rng,
+use std::collections::{hash_map::Entry, HashMap};
+
+use rand::{thread_rng, Rng};
+
+use crate::{
+    distribution::{AvgCUD, Sample, WeightedAvgCUD, CUD, PDF},
+    lookback::FromHistory,
 };
 
-use crate::distribution::{AvgCUD, Sample, WeightedAvgCUD, CUD, PDF};
+pub use blended_markov_distribution_derive::PositionState;
 
 // TODO: look into choose_weighted from random
 // Blended Markov Distribution
@@ -11,17 +16,285 @@ pub struct BMD<Pos: PositionState, Dist> {
     pub distributions: Vec<(Pos, Dist)>,
 }
 
-impl<'a, Pos: PositionState, Dist: 'a> BMD<Pos, Dist> {
-    pub fn interpolate<T>(&'a self, eval_pos: Pos) -> T
+impl<Pos: PositionState, Dist> BMD<Pos, Dist> {
+    pub fn push(&mut self, pos: Pos, dist: Dist) {
+        self.distributions.push((pos, dist));
+    }
+
+    // removes the first `count` pairs, the oldest ones if they were pushed in order
+    pub fn remove_oldest(&mut self, count: usize) {
+        let count = count.min(self.distributions.len());
+        self.distributions.drain(..count);
+    }
+
+    pub fn len(&self) -> usize {
+        self.distributions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.distributions.is_empty()
+    }
+}
+
+// Anything that blends stored (state, distribution) pairs by how similar the states are to a query.
+// BMD, WeightedBMD, OnlineBMD and VariableOrderBMD all do, so every generator written against this
+// works with any of them, per pair weights included.
+pub trait Interpolate {
+    type Pos: PositionState;
+    type Dist;
+
+    fn pairs(&self) -> &[(Self::Pos, Self::Dist)];
+
+    // how much a pair counts for on top of its similarity
+    fn pair_weight(&self, _pair: usize) -> f32 {
+        1.0
+    }
+
+    // the blending weight of one pair at eval_pos, overrides have to agree with weights
+    fn weight_at(&self, pair: usize, eval_pos: &Self::Pos) -> f32 {
+        self.pairs()[pair].0.similarity(eval_pos) * self.pair_weight(pair)
+    }
+
+    // the blending weight of every pair at eval_pos, in the same order as pairs
+    fn weights<'s>(&'s self, eval_pos: &'s Self::Pos) -> impl Iterator<Item = f32> + 's {
+        (0..self.pairs().len()).map(move |i| self.weight_at(i, eval_pos))
+    }
+
+    fn interpolate<'a, T>(&'a self, eval_pos: Self::Pos) -> T
     where
-        T: BlendedDist<'a, &'a Dist> + 'a,
+        Self::Dist: 'a,
+        T: BlendedDist<'a, &'a Self::Dist> + 'a,
     {
-        let weighted_dists = self
-            .distributions
-            .iter()
-            .map(|(pos, dist)| (pos.similarity(&eval_pos), dist));
+        T::from(self.weights(&eval_pos).zip(self.pairs().iter().map(|(_, dist)| dist)))
+    }
+
+    // interpolate into a caller owned workspace, start it off as T::default() and keep reusing it
+    fn interpolate_into<'a, T>(&'a self, eval_pos: &Self::Pos, workspace: &mut T)
+    where
+        Self::Dist: 'a,
+        T: BlendedDist<'a, &'a Self::Dist> + 'a,
+    {
+        workspace.refill(self.weights(eval_pos).zip(self.pairs().iter().map(|(_, dist)| dist)));
+    }
+
+    // Samples `length` frames after `history`, each from the blend at the state `make_state` builds
+    // from the frames so far, newest last, with its weights put through `reweight` first.
+    // One blend is refilled every step, so once it and the weights have grown this doesn't allocate.
+    // Only the new frames are returned.
+    fn rollout<'a, T, Out, F, W, R>(
+        &'a self,
+        history: &[Out],
+        length: usize,
+        mut make_state: F,
+        reweight: &mut W,
+        rng: &mut R,
+    ) -> Vec<Out>
+    where
+        Self::Dist: 'a,
+        T: BlendedDist<'a, &'a Self::Dist, OutputState = Out> + Default + 'a,
+        Out: Clone,
+        F: FnMut(&[Out]) -> Self::Pos,
+        W: Reweight + ?Sized,
+        R: Rng + ?Sized,
+    {
+        let mut frames = history.to_vec();
+        frames.reserve(length);
+        let mut weights = Vec::with_capacity(self.pairs().len());
+        let mut blend = T::default();
+
+        for step in 0..length {
+            let eval_pos = make_state(&frames);
+            weights.clear();
+            weights.extend(self.weights(&eval_pos));
+            reweight.reweight(step, length, &mut weights);
+
+            let dists = self.pairs().iter().map(|(_, dist)| dist);
+            blend.refill(weights.iter().copied().zip(dists));
+            frames.push(blend.sample_with(rng));
+        }
+
+        frames.split_off(history.len())
+    }
+
+    // Runs the model for `length` frames after `history`, only the new frames are returned
+    fn generate<'a, T, const OUT: usize>(
+        &'a self,
+        history: &[[f32; OUT]],
+        length: usize,
+    ) -> Vec<[f32; OUT]>
+    where
+        Self::Pos: FromHistory<OUT>,
+        Self::Dist: 'a,
+        T: BlendedDist<'a, &'a Self::Dist, OutputState = [f32; OUT]> + Default + 'a,
+    {
+        self.generate_reweighted::<T, _, OUT>(history, length, &mut ())
+    }
+
+    // generate, with every step's weights put through `reweight` before they are blended
+    fn generate_reweighted<'a, T, W, const OUT: usize>(
+        &'a self,
+        history: &[[f32; OUT]],
+        length: usize,
+        reweight: &mut W,
+    ) -> Vec<[f32; OUT]>
+    where
+        Self::Pos: FromHistory<OUT>,
+        Self::Dist: 'a,
+        T: BlendedDist<'a, &'a Self::Dist, OutputState = [f32; OUT]> + Default + 'a,
+        W: Reweight + ?Sized,
+    {
+        self.rollout::<T, _, _, _, _>(
+            history,
+            length,
+            Self::Pos::from_history,
+            reweight,
+            &mut thread_rng(),
+        )
+    }
+
+    // For a Direction::Backward model, the `length` frames leading up to `end`.
+    // Both `end` and the result are in normal time order.
+    fn generate_backward<'a, T, const OUT: usize>(
+        &'a self,
+        end: &[[f32; OUT]],
+        length: usize,
+    ) -> Vec<[f32; OUT]>
+    where
+        Self::Pos: FromHistory<OUT>,
+        Self::Dist: 'a,
+        T: BlendedDist<'a, &'a Self::Dist, OutputState = [f32; OUT]> + Default + 'a,
+    {
+        let end_reversed: Vec<_> = end.iter().rev().copied().collect();
+
+        let mut frames = self.generate::<T, OUT>(&end_reversed, length);
+        frames.reverse();
+        frames
+    }
+}
+
+// Changes the blending weights of each step of a rollout, after the similarity pass and before
+// they are blended. Temperature Schedules, Novelty and Diagnose are all Reweights. A pair applies
+// its first part and then its second, nest them to combine more.
+pub trait Reweight {
+    // one weight per pair in the order of Interpolate::pairs, for `step` out of `length`
+    fn reweight(&mut self, step: usize, length: usize, weights: &mut [f32]);
+}
+
+// leaves the weights alone
+impl Reweight for () {
+    fn reweight(&mut self, _step: usize, _length: usize, _weights: &mut [f32]) {}
+}
+
+impl<F: FnMut(&mut [f32])> Reweight for F {
+    fn reweight(&mut self, _step: usize, _length: usize, weights: &mut [f32]) {
+        self(weights)
+    }
+}
+
+impl<A: Reweight, B: Reweight> Reweight for (A, B) {
+    fn reweight(&mut self, step: usize, length: usize, weights: &mut [f32]) {
+        self.0.reweight(step, length, weights);
+        self.1.reweight(step, length, weights);
+    }
+}
+
+impl<Pos: PositionState, Dist> Interpolate for BMD<Pos, Dist> {
+    type Pos = Pos;
+    type Dist = Dist;
+
+    fn pairs(&self) -> &[(Pos, Dist)] {
+        &self.distributions
+    }
+}
+
+// Picks an index in proportion to the weights without allocating, None if they are all zero
+pub fn pick_weighted<R: Rng + ?Sized>(weights: &[f32], rng: &mut R) -> Option<usize> {
+    let total: f32 = weights.iter().sum();
+    if !(total > 0.0 && total.is_finite()) {
+        return None;
+    }
+
+    let cutoff = rng.gen::<f32>() * total;
+    let mut bar = 0.0;
+    for (i, w) in weights.iter().enumerate() {
+        bar += w;
+        if bar > cutoff {
+            return Some(i);
+        }
+    }
+
+    // rounding can leave us just short of the total, fall back to the last one with any weight
+    weights.iter().rposition(|w| *w > 0.0)
+}
+
+// 1/sum(w^2) once the weights are normalized, roughly how many neighbours are really contributing
+pub fn effective_sample_size(weights: &[f32]) -> f32 {
+    let total: f32 = weights.iter().sum();
+    let sum_sq: f32 = weights.iter().map(|w| w * w).sum();
+
+    if total > 0.0 && sum_sq > 0.0 {
+        total * total / sum_sq
+    } else {
+        0.0
+    }
+}
+
+// How the neighbour weights of a blend are spread out. A blend that is just replaying
+// one training frame has an effective sample size near 1, an entropy near 0 and a max weight near 1.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WeightDiagnostics {
+    pub effective_sample_size: f32,
+    // of the normalized weights, in nats
+    pub entropy: f32,
+    // normalized, so out of 1
+    pub max_weight: f32,
+    // how many normalized weights are at least the threshold asked for
+    pub above_threshold: usize,
+}
+
+impl WeightDiagnostics {
+    pub fn of(weights: &[f32], threshold: f32) -> Self {
+        let total: f32 = weights.iter().sum();
+        if !(total > 0.0 && total.is_finite()) {
+            return WeightDiagnostics::default();
+        }
+
+        let normalized = weights.iter().map(|w| w / total);
+
+        WeightDiagnostics {
+            effective_sample_size: effective_sample_size(weights),
+            entropy: normalized
+                .clone()
+                .filter(|p| *p > 0.0)
+                .map(|p| -p * p.ln())
+                .sum(),
+            max_weight: normalized.clone().fold(0.0, f32::max),
+            above_threshold: normalized.filter(|p| *p >= threshold).count(),
+        }
+    }
+}
+
+// Keeps the WeightDiagnostics of every step it sees without changing anything.
+// Put it after any other Reweight to see the weights that really get blended.
+#[derive(Debug, Clone, Default)]
+pub struct Diagnose {
+    // normalized weights of at least this count as contributors
+    pub threshold: f32,
+    pub steps: Vec<WeightDiagnostics>,
+}
+
+impl Diagnose {
+    pub fn new(threshold: f32) -> Self {
+        Diagnose {
+            threshold,
+            steps: Vec::new(),
+        }
+    }
+}
 
-        T::from(weighted_dists)
+impl Reweight for Diagnose {
+    fn reweight(&mut self, _step: usize, _length: usize, weights: &mut [f32]) {
+        self.steps.push(WeightDiagnostics::of(weights, self.threshold));
     }
 }
 
@@ -32,9 +305,37 @@ pub trait BlendedDist<'a, DistRef: 'a> {
     where
         T: IntoIterator<Item = (f32, DistRef)>;
 
+    // Same as from, but reusing this one's buffers so steady state interpolation doesn't allocate.
+    // Rebuilds from scratch by default.
+    fn refill<T>(&mut self, weighted_dists: T)
+    where
+        T: IntoIterator<Item = (f32, DistRef)>,
+        Self: Sized,
+    {
+        *self = Self::from(weighted_dists);
+    }
+
     fn evalutate(&self, eval_pos: Self::OutputState) -> f32;
 
-    fn sample(&self) -> Self::OutputState;
+    fn sample(&self) -> Self::OutputState {
+        self.sample_with(&mut thread_rng())
+    }
+
+    // same as sample, but with a caller supplied rng so runs can be seeded
+    fn sample_with<R: Rng + ?Sized>(&self, rng: &mut R) -> Self::OutputState;
+
+    // the weight of each neighbour blended, in the order they were given, not necessarily normalized
+    fn neighbour_weights(&self) -> &[f32];
+
+    // neighbour weights of at least `threshold` out of 1 count as contributors
+    fn diagnostics(&self, threshold: f32) -> WeightDiagnostics {
+        WeightDiagnostics::of(self.neighbour_weights(), threshold)
+    }
+
+    // a handful of likely outputs to try when searching instead of sampling, just samples by default
+    fn candidates(&self, count: usize) -> Vec<Self::OutputState> {
+        (0..count).map(|_| self.sample()).collect()
+    }
 }
 
 pub trait PositionState {
@@ -61,6 +362,7 @@ impl PositionState for Lookback12 {
     }
 }
 
+#[derive(Debug, Clone, Copy)]
 pub struct RExp(pub f32);
 
 impl PositionState for RExp {
@@ -82,27 +384,29 @@ impl<'a> BlendedDist<'a, &'a AvgCUD> for WeightedAvgCUD<'a> {
     where
         T: IntoIterator<Item = (f32, &'a AvgCUD)>,
     {
-        let mut weights = Vec::new();
-        let mut dists = Vec::new();
-        // .unzip was being weird, so I did this
-        for (w, d) in weighted_dists.into_iter() {
-            weights.push(w);
-            dists.push(d);
-        }
+        let mut weighted = WeightedAvgCUD::default();
+        weighted.refill(weighted_dists);
+        weighted
+    }
 
+    fn refill<T>(&mut self, weighted_dists: T)
+    where
+        T: IntoIterator<Item = (f32, &'a AvgCUD)>,
+    {
         // normalize the weights, similarities between positionstates aren't garunteed to be normalized
-        let total: f32 = weights.iter().sum();
-        weights.iter_mut().for_each(|w| *w /= total);
-
-        WeightedAvgCUD::from_weighted_avgcuds(&dists, &weights)
+        self.refill_weighted_avgcuds(weighted_dists);
     }
 
     fn evalutate(&self, eval_pos: f32) -> f32 {
-        <WeightedAvgCUD as PDF>::evaluate(&self, eval_pos)
+        <WeightedAvgCUD as PDF>::evaluate(self, eval_pos)
     }
 
-    fn sample(&self) -> f32 {
-        <WeightedAvgCUD<'_> as Sample>::sample(&self)
+    fn neighbour_weights(&self) -> &[f32] {
+        self.weights()
+    }
+
+    fn sample_with<R: Rng + ?Sized>(&self, rng: &mut R) -> f32 {
+        <WeightedAvgCUD<'_> as Sample>::sample_with(self, rng)
     }
 }
 
@@ -130,6 +434,18 @@ impl<'a> BlendedDist<'a, &'a CUD> for WeightedCUDs<'a> {
         WeightedCUDs { weights, dists }
     }
 
+    fn refill<T>(&mut self, weighted_dists: T)
+    where
+        T: IntoIterator<Item = (f32, &'a CUD)>,
+    {
+        self.weights.clear();
+        self.dists.clear();
+        for (w, d) in weighted_dists.into_iter() {
+            self.weights.push(w);
+            self.dists.push(d);
+        }
+    }
+
     fn evalutate(&self, eval_pos: f32) -> f32 {
         // because we don't normalize weights, we have to calculate a sum here
         self.weights
@@ -140,20 +456,24 @@ impl<'a> BlendedDist<'a, &'a CUD> for WeightedCUDs<'a> {
             / self.weights.iter().sum::<f32>()
     }
 
-    fn sample(&self) -> f32 {
-        let index = WeightedIndex::new(&self.weights).unwrap();
-        let mut rng = thread_rng();
+    fn neighbour_weights(&self) -> &[f32] {
+        &self.weights
+    }
+
+    fn sample_with<R: Rng + ?Sized>(&self, rng: &mut R) -> f32 {
+        let index = pick_weighted(&self.weights, rng).unwrap();
 
-        self.dists[index.sample(&mut rng)].sample()
+        self.dists[index].sample_with(rng)
     }
 }
 
+#[derive(Default)]
 pub struct WeightedCUDs<'a> {
     weights: Vec<f32>,
     dists: Vec<&'a CUD>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct SpikeDist<O> {
     pub pos: O,
     pub side_len: f32,
@@ -161,12 +481,36 @@ pub struct SpikeDist<O> {
 
 impl<const N: usize> SpikeDist<[f32;N]>
 {
-    fn sample(&self) -> [f32;N] {
-        let nudge = |inp: f32| inp + (self.side_len / 2.0) * (1.0 - random::<f32>() * 2.0);
+    pub(crate) fn sample_with<R: Rng + ?Sized>(&self, rng: &mut R) -> [f32;N] {
+        let nudge = |inp: f32| inp + (self.side_len / 2.0) * (1.0 - rng.gen::<f32>() * 2.0);
 
         self.pos.map(nudge)
     }
 
+    // uniform over the box, a zero sized box is a point mass so we give back its probability instead
+    pub(crate) fn evaluate(&self, x: &[f32;N]) -> f32 {
+        let half = self.side_len / 2.0;
+        if self.pos.iter().zip(x).any(|(p, x)| (p - x).abs() > half) {
+            return 0.0;
+        }
+
+        if self.side_len > 0.0 {
+            1.0 / self.side_len.powi(N as i32)
+        } else {
+            1.0
+        }
+    }
+
+}
+
+// not derived, that would need Out: Default
+impl<Out> Default for WeightedSpikes<'_, Out> {
+    fn default() -> Self {
+        WeightedSpikes {
+            dists: Vec::new(),
+            weights: Vec::new(),
+        }
+    }
 }
 
 pub struct WeightedSpikes<'a, Out> {
@@ -193,17 +537,146 @@ impl<'a, const N: usize> BlendedDist<'a, &'a SpikeDist<[f32;N]>> for WeightedSpi
         WeightedSpikes { weights, dists }
     }
 
+    fn refill<T>(&mut self, weighted_dists: T)
+    where
+        T: IntoIterator<Item = (f32, &'a SpikeDist<[f32;N]>)>,
+    {
+        self.weights.clear();
+        self.dists.clear();
+        for (w, d) in weighted_dists.into_iter() {
+            self.weights.push(w);
+            self.dists.push(d);
+        }
+    }
+
     fn evalutate(&self, eval_pos: [f32;N]) -> f32 {
-        unimplemented!()
+        self.weights
+            .iter()
+            .zip(&self.dists)
+            .map(|(w, spike)| spike.evaluate(&eval_pos) * w)
+            .sum::<f32>()
+            / self.weights.iter().sum::<f32>()
+    }
+
+    fn neighbour_weights(&self) -> &[f32] {
+        &self.weights
     }
 
-    fn sample(&self) -> [f32;N] {
-        let index = match WeightedIndex::new(&self.weights) {
-            Ok(i) => i,
-            Err(e) => panic!("We couldn't find anything similar!"),
+    fn sample_with<R: Rng + ?Sized>(&self, rng: &mut R) -> [f32;N] {
+        let index = match pick_weighted(&self.weights, rng) {
+            Some(i) => i,
+            None => panic!("We couldn't find anything similar!"),
         };
-        let mut rng = thread_rng();
 
-        self.dists[index.sample(&mut rng)].sample()
+        self.dists[index].sample_with(rng)
+    }
+
+    // the spike centres with the most weight, spikes in the same spot are counted together
+    fn candidates(&self, count: usize) -> Vec<[f32;N]> {
+        // keyed on the bits so merging is linear, + 0.0 turns -0.0 into 0.0 so they still merge
+        let mut seen: HashMap<[u32;N], usize> = HashMap::new();
+        let mut centres: Vec<([f32;N], f32)> = Vec::new();
+        for (spike, w) in self.dists.iter().zip(&self.weights) {
+            match seen.entry(spike.pos.map(|x| (x + 0.0).to_bits())) {
+                Entry::Occupied(i) => centres[*i.get()].1 += w,
+                Entry::Vacant(slot) => {
+                    slot.insert(centres.len());
+                    centres.push((spike.pos, *w));
+                }
+            }
+        }
+
+        let heaviest_first = |a: &([f32;N], f32), b: &([f32;N], f32)| b.1.total_cmp(&a.1);
+        if count < centres.len() {
+            centres.select_nth_unstable_by(count, heaviest_first);
+            centres.truncate(count);
+        }
+        centres.sort_by(heaviest_first);
+
+        centres.into_iter().map(|(pos, _)| pos).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lookback::Lookback;
+
+    #[test]
+    fn candidates_merge_spikes_in_the_same_spot() {
+        let spike = |x: f32| SpikeDist { pos: [x], side_len: 0.0 };
+        let (a, b, c) = (spike(1.0), spike(2.0), spike(3.0));
+        let (negative_zero, zero) = (spike(-0.0), spike(0.0));
+        let blend: WeightedSpikes<[f32; 1]> = BlendedDist::from([
+            (0.3, &a),
+            (0.4, &b),
+            (0.3, &a),
+            (0.1, &c),
+            (0.05, &negative_zero),
+            (0.05, &zero),
+        ]);
+
+        assert_eq!(blend.candidates(2), vec![[1.0], [2.0]]);
+        assert_eq!(blend.candidates(10), vec![[1.0], [2.0], [3.0], [0.0]]);
+    }
+
+    #[test]
+    fn reweights_apply_in_order_and_see_every_step() {
+        let bmd = BMD {
+            distributions: (0..4)
+                .map(|i| (Lookback([[0.0]]), SpikeDist { pos: [i as f32], side_len: 0.0 }))
+                .collect(),
+        };
+
+        // only the last pair survives, and the diagnostics after it see a single neighbour
+        let only_last = |weights: &mut [f32]| {
+            let last = weights.len() - 1;
+            weights[..last].fill(0.0);
+        };
+        let mut reweight = (only_last, Diagnose::new(0.5));
+        let frames =
+            bmd.generate_reweighted::<WeightedSpikes<[f32; 1]>, _, 1>(&[[0.0]], 5, &mut reweight);
+
+        assert_eq!(frames, vec![[3.0]; 5]);
+        assert_eq!(reweight.1.steps.len(), 5);
+        assert!(reweight
+            .1
+            .steps
+            .iter()
+            .all(|d| d.effective_sample_size == 1.0 && d.above_threshold == 1));
+    }
+
+    #[test]
+    fn refill_matches_from_and_keeps_its_buffers() {
+        let spikes: Vec<_> = (0..6).map(|i| SpikeDist { pos: [i as f32], side_len: 0.0 }).collect();
+        let weighted = || spikes.iter().enumerate().map(|(i, spike)| (i as f32 * 0.1, spike));
+
+        let fresh: WeightedSpikes<[f32; 1]> = BlendedDist::from(weighted());
+        let mut workspace = WeightedSpikes::default();
+        workspace.refill(weighted());
+        assert_eq!(workspace.neighbour_weights(), fresh.neighbour_weights());
+        assert_eq!(workspace.candidates(6), fresh.candidates(6));
+
+        // refilling with as many neighbours again reuses what is there
+        let capacity = (workspace.weights.capacity(), workspace.dists.capacity());
+        for _ in 0..10 {
+            workspace.refill(weighted());
+        }
+        assert_eq!((workspace.weights.capacity(), workspace.dists.capacity()), capacity);
+        assert_eq!(workspace.neighbour_weights(), fresh.neighbour_weights());
+    }
+
+    #[test]
+    fn refill_matches_from_for_avgcuds() {
+        let avg = |a: f32| AvgCUD { cuds: vec![CUD { a, b: a + 1.0 }] };
+        let (x, y) = (avg(0.0), avg(2.0));
+
+        let fresh: WeightedAvgCUD = BlendedDist::from([(1.0, &x), (3.0, &y)]);
+        let mut workspace = WeightedAvgCUD::default();
+        workspace.refill([(2.0, &y), (2.0, &x), (2.0, &y)]);
+        workspace.refill([(1.0, &x), (3.0, &y)]);
+
+        assert_eq!(workspace.neighbour_weights(), fresh.neighbour_weights());
+        assert_eq!(BlendedDist::evalutate(&workspace, 2.5), BlendedDist::evalutate(&fresh, 2.5));
     }
 }
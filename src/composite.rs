@@ -0,0 +1,109 @@
+use crate::bmd::PositionState;
+
+// Combines component similarities as exp(sum of weight * ln(similarity)).
+// With every weight at 1.0 this is just the product of the similarities.
+pub fn weighted_log_similarity<I>(parts: I) -> f32
+where
+    I: IntoIterator<Item = (f32, f32)>,
+{
+    let mut log_sim = 0.0;
+    for (weight, sim) in parts {
+        if weight == 0.0 {
+            continue;
+        }
+        if sim <= 0.0 {
+            // ln(0) would give us a NaN once multiplied through, the whole thing is just dissimilar
+            return 0.0;
+        }
+        log_sim += weight * sim.ln();
+    }
+
+    f32::exp(log_sim)
+}
+
+// Scales how much a component of a tuple state counts, as a power on its similarity.
+// Both sides have to carry the same weight, otherwise similarity wouldn't be symmetric.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Weighted<S>(pub S, pub f32);
+
+impl<S: PositionState> PositionState for Weighted<S> {
+    fn similarity(&self, other: &Self) -> f32 {
+        assert_eq!(self.1, other.1, "Weighted states compared with different weights");
+
+        weighted_log_similarity([(self.1, self.0.similarity(&other.0))])
+    }
+}
+
+// tuples of states multiply their component similarities
+macro_rules! impl_tuple_state {
+    ($($name:ident $idx:tt),+) => {
+        impl<$($name: PositionState),+> PositionState for ($($name,)+) {
+            fn similarity(&self, other: &Self) -> f32 {
+                weighted_log_similarity([$((1.0, self.$idx.similarity(&other.$idx))),+])
+            }
+        }
+    };
+}
+
+impl_tuple_state!(A 0, B 1);
+impl_tuple_state!(A 0, B 1, C 2);
+impl_tuple_state!(A 0, B 1, C 2, D 3);
+impl_tuple_state!(A 0, B 1, C 2, D 3, E 4);
+impl_tuple_state!(A 0, B 1, C 2, D 3, E 4, F 5);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bmd::RExp;
+
+    #[test]
+    fn weighted_is_symmetric() {
+        let a = Weighted(RExp(0.0), 2.0);
+        let b = Weighted(RExp(0.1), 2.0);
+
+        assert_eq!(a.similarity(&b), b.similarity(&a));
+    }
+
+    #[test]
+    #[should_panic(expected = "different weights")]
+    fn weighted_rejects_mismatched_weights() {
+        Weighted(RExp(0.0), 2.0).similarity(&Weighted(RExp(0.1), 1.0));
+    }
+
+    fn absolute(a: &f32, b: &f32) -> f32 {
+        f32::exp(-(a - b).abs())
+    }
+
+    // generic without a where clause, the derive adds S: PositionState itself
+    #[derive(Debug, Clone, Copy, PositionState)]
+    struct Mixed<S> {
+        #[similarity(weight = 2.0)]
+        state: S,
+        #[similarity(metric = absolute)]
+        value: f32,
+        #[similarity(skip)]
+        label: &'static str,
+    }
+
+    #[test]
+    fn derive_weights_swaps_metrics_and_skips() {
+        let a = Mixed {
+            state: RExp(0.0),
+            value: 1.0,
+            label: "a",
+        };
+        let b = Mixed {
+            state: RExp(0.1),
+            value: 1.5,
+            label: "b",
+        };
+
+        let expected = RExp(0.0).similarity(&RExp(0.1)).powi(2) * f32::exp(-0.5);
+        assert!((a.similarity(&b) - expected).abs() < 1e-6);
+
+        // the label doesn't count at all
+        let relabelled = Mixed { label: "c", ..a };
+        assert_eq!(a.similarity(&relabelled), 1.0);
+        assert_eq!(relabelled.label, "c");
+    }
+}
@@ -0,0 +1,278 @@
+use crate::{
+    bmd::{PositionState, SpikeDist, BMD},
+    distribution::{AvgCUD, CUD},
+    owned::Component,
+    weighted::WeightedBMD,
+};
+
+// A stored distribution that a group of similar ones can be merged into
+pub trait Prototype: Component + Sized {
+    // one distribution standing in for all the weighted members
+    fn merge(members: &[(f32, &Self)]) -> Self;
+
+    // a typical output, used to check how well the condensed model still explains the training data
+    fn centre(&self) -> Self::OutputState;
+
+    // whether evaluate gives a probability rather than a density, which can't be scored against a merge
+    fn is_point_mass(&self) -> bool {
+        false
+    }
+}
+
+// the weighted mean, in a box big enough to still cover every member's box
+impl<const N: usize> Prototype for SpikeDist<[f32; N]> {
+    fn merge(members: &[(f32, &Self)]) -> Self {
+        let total: f32 = members.iter().map(|(w, _)| w).sum();
+
+        let mut pos = [0.0; N];
+        for (w, spike) in members {
+            for (p, x) in pos.iter_mut().zip(spike.pos) {
+                *p += x * w / total;
+            }
+        }
+
+        let side_len = members
+            .iter()
+            .flat_map(|(_, spike)| {
+                pos.iter()
+                    .zip(spike.pos)
+                    .map(|(p, x)| 2.0 * (p - x).abs() + spike.side_len)
+            })
+            .fold(0.0, f32::max);
+
+        SpikeDist { pos, side_len }
+    }
+
+    fn centre(&self) -> [f32; N] {
+        self.pos
+    }
+
+    fn is_point_mass(&self) -> bool {
+        self.side_len <= 0.0
+    }
+}
+
+// covers every member
+impl Prototype for CUD {
+    fn merge(members: &[(f32, &Self)]) -> Self {
+        CUD {
+            a: members
+                .iter()
+                .map(|(_, c)| c.a)
+                .fold(f32::INFINITY, f32::min),
+            b: members
+                .iter()
+                .map(|(_, c)| c.b)
+                .fold(f32::NEG_INFINITY, f32::max),
+        }
+    }
+
+    fn centre(&self) -> f32 {
+        (self.a + self.b) / 2.0
+    }
+}
+
+// keeps every member's pieces, so nothing is lost but the stored state
+impl Prototype for AvgCUD {
+    fn merge(members: &[(f32, &Self)]) -> Self {
+        AvgCUD {
+            cuds: members
+                .iter()
+                .flat_map(|(_, avg)| avg.cuds.iter().cloned())
+                .collect(),
+        }
+    }
+
+    fn centre(&self) -> f32 {
+        self.cuds.iter().map(Prototype::centre).sum::<f32>() / self.cuds.len() as f32
+    }
+}
+
+// How much condensing changed the model
+#[derive(Debug, Clone, Copy)]
+pub struct CondenseReport {
+    pub before: usize,
+    pub after: usize,
+    // mean over the training pairs of how much less likely their outputs got, in nats.
+    // None when the training distributions are point masses, their probabilities and the merged
+    // densities aren't in the same units
+    pub log_likelihood_lost: Option<f32>,
+}
+
+// Greedy coverage: each pair joins the most similar prototype so far if that is at least
+// min_similarity, otherwise it starts a new one. The prototype keeps its first pair's state
+// and is weighted by how many of the original pairs it stands for.
+#[derive(Debug, Clone, Copy)]
+pub struct Condense {
+    pub min_similarity: f32,
+}
+
+impl Condense {
+    pub fn new(min_similarity: f32) -> Self {
+        Condense { min_similarity }
+    }
+
+    // which prototype every pair belongs to, and the pair each prototype was started from
+    fn cover<Pos: PositionState, Dist>(&self, bmd: &BMD<Pos, Dist>) -> (Vec<usize>, Vec<usize>) {
+        let mut seeds: Vec<usize> = Vec::new();
+        let mut assignment = Vec::with_capacity(bmd.len());
+
+        for (pos, _) in &bmd.distributions {
+            let best = seeds
+                .iter()
+                .enumerate()
+                .map(|(k, &seed)| (k, bmd.distributions[seed].0.similarity(pos)))
+                .max_by(|a, b| a.1.total_cmp(&b.1))
+                .filter(|(_, similarity)| *similarity >= self.min_similarity);
+
+            match best {
+                Some((k, _)) => assignment.push(k),
+                None => {
+                    assignment.push(seeds.len());
+                    seeds.push(assignment.len() - 1);
+                }
+            }
+        }
+
+        (assignment, seeds)
+    }
+
+    pub fn run<Pos, Dist>(&self, bmd: BMD<Pos, Dist>) -> (WeightedBMD<Pos, Dist>, CondenseReport)
+    where
+        Pos: PositionState,
+        Dist: Prototype,
+    {
+        let (assignment, seeds) = self.cover(&bmd);
+
+        let mut members: Vec<Vec<(f32, &Dist)>> = vec![Vec::new(); seeds.len()];
+        for ((_, dist), &k) in bmd.distributions.iter().zip(&assignment) {
+            members[k].push((1.0, dist));
+        }
+
+        let merged: Vec<Dist> = members.iter().map(|m| Dist::merge(m)).collect();
+        let weights: Vec<f32> = members.iter().map(|m| m.len() as f32).collect();
+
+        let report = CondenseReport {
+            before: bmd.len(),
+            after: seeds.len(),
+            log_likelihood_lost: (!bmd.distributions.iter().any(|(_, d)| d.is_point_mass()))
+                .then(|| log_likelihood_lost(&bmd, &seeds, &merged, &weights)),
+        };
+
+        // seeds are in increasing order, so the kept states line up with the prototypes
+        let states = bmd
+            .distributions
+            .into_iter()
+            .enumerate()
+            .filter(|(i, _)| seeds.binary_search(i).is_ok())
+            .map(|(_, (pos, _))| pos);
+
+        let condensed = WeightedBMD {
+            bmd: BMD {
+                distributions: states.zip(merged).collect(),
+            },
+            weights,
+        };
+
+        (condensed, report)
+    }
+}
+
+// each training pair's output centre scored against the blend at its own state, before and after
+fn log_likelihood_lost<Pos: PositionState, Dist: Prototype>(
+    bmd: &BMD<Pos, Dist>,
+    seeds: &[usize],
+    merged: &[Dist],
+    weights: &[f32],
+) -> f32 {
+    let lost: f32 =
+        bmd.distributions
+            .iter()
+            .map(|(at, dist)| {
+                let x = dist.centre();
+
+                let before = log_blend(
+                    bmd.distributions
+                        .iter()
+                        .map(|(pos, d)| (pos.similarity(at), d.evaluate(&x))),
+                );
+                let after = log_blend(seeds.iter().zip(merged).zip(weights).map(
+                    |((&seed, d), w)| {
+                        (w * bmd.distributions[seed].0.similarity(at), d.evaluate(&x))
+                    },
+                ));
+
+                before - after
+            })
+            .sum();
+
+    lost / bmd.len().max(1) as f32
+}
+
+// log of the weighted mean of (weight, density) pairs
+fn log_blend(weighted: impl Iterator<Item = (f32, f32)>) -> f32 {
+    let (density, total) = weighted.fold((0.0, 0.0), |(d, t), (w, p)| (d + w * p, t + w));
+    (density / total).ln()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lookback::Lookback;
+
+    fn spikes(side_len: f32) -> BMD<Lookback<1, 1>, SpikeDist<[f32; 1]>> {
+        let pair = |state: f32, out: f32| {
+            (
+                Lookback([[state]]),
+                SpikeDist {
+                    pos: [out],
+                    side_len,
+                },
+            )
+        };
+        BMD {
+            distributions: vec![
+                pair(0.0, 1.0),
+                pair(5.0, 3.0),
+                pair(0.01, 1.02),
+                pair(0.02, 1.06),
+                pair(5.01, 3.04),
+            ],
+        }
+    }
+
+    #[test]
+    fn run_merges_each_group_into_its_first_state() {
+        let bmd = spikes(0.1);
+        let original = bmd.distributions.clone();
+
+        let (condensed, report) = Condense::new(0.5).run(bmd);
+
+        assert_eq!((report.before, report.after), (5, 2));
+        assert_eq!(condensed.weights, [3.0, 2.0]);
+        // prototypes keep the state of the pair that started them
+        assert_eq!(condensed.bmd.distributions[0].0, original[0].0);
+        assert_eq!(condensed.bmd.distributions[1].0, original[1].0);
+
+        // every member's box fits inside its prototype's
+        for (k, members) in [[0, 2, 3].as_slice(), &[1, 4]].into_iter().enumerate() {
+            let merged = &condensed.bmd.distributions[k].1;
+            for &i in members {
+                let member = &original[i].1;
+                let reach = (merged.pos[0] - member.pos[0]).abs() + member.side_len / 2.0;
+                assert!(reach <= merged.side_len / 2.0 + 1e-6);
+            }
+        }
+
+        assert!(report.log_likelihood_lost.unwrap() >= 0.0);
+    }
+
+    #[test]
+    fn point_masses_get_no_likelihood_report() {
+        let (condensed, report) = Condense::new(0.5).run(spikes(0.0));
+
+        assert_eq!(report.after, 2);
+        assert!(condensed.bmd.distributions[0].1.side_len > 0.0);
+        assert_eq!(report.log_likelihood_lost, None);
+    }
+}
@@ -0,0 +1,109 @@
+use rand::thread_rng;
+
+use crate::{
+    bmd::{BlendedDist, Interpolate, PositionState, SpikeDist, BMD},
+    lookback::FromHistory,
+};
+
+// A history state along with the control input for the frame being predicted.
+// During training the control comes from labelled data, when generating the caller supplies it,
+// so interpolating conditions on both. Wrap the control in composite::Weighted to change how much it counts.
+#[derive(Debug, Clone, Copy, PartialEq, PositionState)]
+pub struct Controlled<S, C>
+where
+    S: PositionState,
+    C: PositionState,
+{
+    pub history: S,
+    pub control: C,
+}
+
+// A control input with N channels, like a target direction
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Control<const N: usize>(pub [f32; N]);
+
+impl<const N: usize> PositionState for Control<N> {
+    fn similarity(&self, other: &Self) -> f32 {
+        let distance_sq = self
+            .0
+            .iter()
+            .zip(other.0.iter())
+            .map(|(u, v)| (u - v) * (u - v))
+            .sum::<f32>();
+
+        const CONTROL_WIDENESS: f32 = 0.05;
+
+        f32::exp(-distance_sq / CONTROL_WIDENESS)
+    }
+}
+
+impl<S, C, const OUT: usize> BMD<Controlled<S, C>, SpikeDist<[f32; OUT]>>
+where
+    S: PositionState + FromHistory<OUT>,
+    C: PositionState + Clone,
+{
+    // controls[i] is the control that was applied when series[i] happened
+    pub fn from_controlled_series(series: &[[f32; OUT]], controls: &[C], side_len: f32) -> Self {
+        assert_eq!(series.len(), controls.len());
+
+        let distributions = series
+            .windows(S::SPAN + 1)
+            .zip(&controls[S::SPAN..])
+            .map(|(window, control)| {
+                (
+                    Controlled {
+                        history: S::from_history(&window[..S::SPAN]),
+                        control: control.clone(),
+                    },
+                    SpikeDist {
+                        pos: window[S::SPAN],
+                        side_len,
+                    },
+                )
+            })
+            .collect();
+
+        BMD { distributions }
+    }
+}
+
+// Rollouts of any model over Controlled states, steered one control per frame
+pub trait ControlledRollout<S: PositionState, C: PositionState>:
+    Interpolate<Pos = Controlled<S, C>>
+{
+    // Generates one frame per control, starting after `history`.
+    // Only the new frames are returned.
+    fn generate_controlled<'a, T, const OUT: usize>(
+        &'a self,
+        history: &[[f32; OUT]],
+        controls: impl IntoIterator<Item = C>,
+    ) -> Vec<[f32; OUT]>
+    where
+        S: FromHistory<OUT>,
+        Self::Dist: 'a,
+        T: BlendedDist<'a, &'a Self::Dist, OutputState = [f32; OUT]> + Default + 'a,
+    {
+        let controls: Vec<C> = controls.into_iter().collect();
+        let length = controls.len();
+        let mut controls = controls.into_iter();
+
+        self.rollout::<T, _, _, _, _>(
+            history,
+            length,
+            |frames| Controlled {
+                history: S::from_history(frames),
+                control: controls.next().unwrap(),
+            },
+            &mut (),
+            &mut thread_rng(),
+        )
+    }
+}
+
+impl<M, S, C> ControlledRollout<S, C> for M
+where
+    M: Interpolate<Pos = Controlled<S, C>>,
+    S: PositionState,
+    C: PositionState,
+{
+}
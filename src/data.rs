@@ -1,3 +1,6 @@
+// Raw export, kept at the precision it was written with
+#![allow(clippy::excessive_precision)]
+
 pub fn sword_6() -> Vec<[f32; 6]> {
     vec![
         [
@@ -0,0 +1,135 @@
+use crate::{
+    bmd::{PositionState, SpikeDist, BMD},
+    data,
+    lookback::FromHistory,
+    weighted::WeightedBMD,
+};
+
+// One continuous take, its weight multiplies every pair trained from it
+#[derive(Debug, Clone)]
+pub struct Clip<const OUT: usize> {
+    pub name: String,
+    pub frames: Vec<[f32; OUT]>,
+    pub weight: f32,
+}
+
+// Many named clips of any length. Training windows never run from one clip into the next.
+#[derive(Debug, Clone, Default)]
+pub struct Dataset<const OUT: usize> {
+    pub clips: Vec<Clip<OUT>>,
+}
+
+// Where a stored pair came from: the clip, and the frame in it that the pair predicts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Source {
+    pub clip: usize,
+    pub frame: usize,
+}
+
+// A model trained from a Dataset, with the source of every stored pair
+#[derive(Debug)]
+pub struct ClipBMD<Pos: PositionState, const OUT: usize> {
+    pub model: WeightedBMD<Pos, SpikeDist<[f32; OUT]>>,
+    // one per pair, in the same order as the model
+    pub sources: Vec<Source>,
+}
+
+impl<const OUT: usize> Dataset<OUT> {
+    pub fn new() -> Self {
+        Dataset { clips: Vec::new() }
+    }
+
+    pub fn push(&mut self, name: impl Into<String>, frames: Vec<[f32; OUT]>) {
+        self.push_weighted(name, frames, 1.0);
+    }
+
+    pub fn push_weighted(&mut self, name: impl Into<String>, frames: Vec<[f32; OUT]>, weight: f32) {
+        self.clips.push(Clip {
+            name: name.into(),
+            frames,
+            weight,
+        });
+    }
+
+    pub fn clip(&self, name: &str) -> Option<&Clip<OUT>> {
+        self.clips.iter().find(|clip| clip.name == name)
+    }
+
+    pub fn len(&self) -> usize {
+        self.clips.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.clips.is_empty()
+    }
+
+    // whole clips go one way or the other, so nothing validated on was trained on.
+    // Gives back (training, validation).
+    pub fn split<F: Fn(&Clip<OUT>) -> bool>(self, is_validation: F) -> (Self, Self) {
+        let (validation, training) = self.clips.into_iter().partition(is_validation);
+        (Dataset { clips: training }, Dataset { clips: validation })
+    }
+
+    // clips too short for a single window of Pos::SPAN frames plus one to predict add nothing
+    pub fn train<Pos>(&self, side_len: f32) -> ClipBMD<Pos, OUT>
+    where
+        Pos: PositionState + FromHistory<OUT>,
+    {
+        let mut model = WeightedBMD::uniform(BMD {
+            distributions: Vec::new(),
+        });
+        let mut sources = Vec::new();
+
+        for (c, clip) in self.clips.iter().enumerate() {
+            let bmd = BMD::<Pos, _>::from_series(&clip.frames, side_len);
+
+            sources.extend((0..bmd.len()).map(|i| Source {
+                clip: c,
+                frame: i + Pos::SPAN,
+            }));
+            model.append(bmd, clip.weight);
+        }
+
+        ClipBMD { model, sources }
+    }
+}
+
+impl Dataset<6> {
+    // the one sword swing there is so far
+    pub fn sword() -> Self {
+        let mut dataset = Dataset::new();
+        dataset.push("sword_6", data::sword_6());
+        dataset
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lookback::Lookback;
+
+    #[test]
+    fn train_keeps_windows_inside_their_clips() {
+        let mut dataset = Dataset::new();
+        dataset.push("a", (0..5).map(|i| [i as f32]).collect());
+        dataset.push("too short", vec![[50.0], [51.0]]);
+        dataset.push_weighted("b", (0..4).map(|i| [100.0 + i as f32]).collect(), 2.0);
+
+        let trained = dataset.train::<Lookback<2, 1>>(0.0);
+
+        // 5 - 2 pairs from the first clip, none from the second and 4 - 2 from the third
+        let expected: Vec<Source> = [(0, 2), (0, 3), (0, 4), (2, 2), (2, 3)]
+            .into_iter()
+            .map(|(clip, frame)| Source { clip, frame })
+            .collect();
+        assert_eq!(trained.sources, expected);
+        assert_eq!(trained.model.weights, [1.0, 1.0, 1.0, 2.0, 2.0]);
+
+        // each pair predicts its source frame from the two before it, in the same clip
+        for ((pos, spike), source) in trained.model.bmd.distributions.iter().zip(&trained.sources) {
+            let frames = &dataset.clips[source.clip].frames;
+            assert_eq!(spike.pos, frames[source.frame]);
+            assert_eq!(pos.0, [frames[source.frame - 2], frames[source.frame - 1]]);
+        }
+    }
+}
@@ -0,0 +1,145 @@
+use crate::{
+    bmd::{PositionState, SpikeDist, BMD},
+    lookback::FromHistory,
+    weighted::WeightedBMD,
+};
+
+// What happens to a training pair that repeats one already kept
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicateAction {
+    // fold it into the kept pair, which then counts once more when blending
+    Merge,
+    // throw it away, the kept pair still counts once
+    Drop,
+}
+
+// Finds training pairs that repeat an earlier one, like the run of identical frames sword_6 starts with.
+// A pair is a duplicate when its state is at least min_similarity to a kept pair's state
+// and no output channel is further than tolerance from that pair's output.
+#[derive(Debug, Clone, Copy)]
+pub struct Dedup {
+    pub min_similarity: f32,
+    pub tolerance: f32,
+    pub action: DuplicateAction,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DedupSummary {
+    pub before: usize,
+    pub after: usize,
+    // how many pairs were merged or dropped
+    pub duplicates: usize,
+}
+
+impl Dedup {
+    // only pairs identical to one already kept
+    pub fn exact(action: DuplicateAction) -> Self {
+        Dedup {
+            min_similarity: 1.0,
+            tolerance: 0.0,
+            action,
+        }
+    }
+
+    pub fn run<Pos: PositionState, const OUT: usize>(
+        &self,
+        bmd: BMD<Pos, SpikeDist<[f32; OUT]>>,
+    ) -> (WeightedBMD<Pos, SpikeDist<[f32; OUT]>>, DedupSummary) {
+        let mut kept: WeightedBMD<Pos, SpikeDist<[f32; OUT]>> = WeightedBMD {
+            bmd: BMD {
+                distributions: Vec::new(),
+            },
+            weights: Vec::new(),
+        };
+        let mut summary = DedupSummary {
+            before: bmd.len(),
+            ..Default::default()
+        };
+
+        for (pos, spike) in bmd.distributions {
+            let original = kept.bmd.distributions.iter().position(|(p, s)| {
+                s.pos
+                    .iter()
+                    .zip(&spike.pos)
+                    .all(|(a, b)| (a - b).abs() <= self.tolerance)
+                    && p.similarity(&pos) >= self.min_similarity
+            });
+
+            match original {
+                Some(i) => {
+                    summary.duplicates += 1;
+                    if self.action == DuplicateAction::Merge {
+                        kept.weights[i] += 1.0;
+                    }
+                }
+                None => {
+                    kept.bmd.push(pos, spike);
+                    kept.weights.push(1.0);
+                }
+            }
+        }
+
+        summary.after = kept.len();
+        (kept, summary)
+    }
+}
+
+impl<Pos: PositionState + FromHistory<OUT>, const OUT: usize> BMD<Pos, SpikeDist<[f32; OUT]>> {
+    // from_series, with repeated pairs merged or dropped as it goes
+    pub fn from_series_dedup(
+        series: &[[f32; OUT]],
+        side_len: f32,
+        dedup: &Dedup,
+    ) -> (WeightedBMD<Pos, SpikeDist<[f32; OUT]>>, DedupSummary) {
+        dedup.run(Self::from_series(series, side_len))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lookback::Lookback;
+
+    // still for 4 frames, a step, still for 4 more, then the step again but slightly off
+    fn series() -> Vec<[f32; 1]> {
+        [0.0, 0.0, 0.0, 0.0, 1.0, 1.0, 1.0, 1.0, 1.0, 0.0005, 0.0005, 1.0005]
+            .map(|x| [x])
+            .to_vec()
+    }
+
+    fn dedup(
+        dedup: &Dedup,
+    ) -> (
+        WeightedBMD<Lookback<2, 1>, SpikeDist<[f32; 1]>>,
+        DedupSummary,
+    ) {
+        BMD::from_series_dedup(&series(), 0.0, dedup)
+    }
+
+    #[test]
+    fn exact_duplicates_merge_into_weights() {
+        let (model, summary) = dedup(&Dedup::exact(DuplicateAction::Merge));
+
+        // (0,0)->0 twice and (1,1)->1 three times collapse, everything else is kept
+        assert_eq!(summary.before, 10);
+        assert_eq!(summary.duplicates, 3);
+        assert_eq!(summary.after, 7);
+        assert_eq!(model.len(), 7);
+        assert_eq!(model.weights, [2.0, 1.0, 1.0, 3.0, 1.0, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn near_duplicates_are_dropped() {
+        let near = Dedup {
+            min_similarity: 0.9,
+            tolerance: 0.01,
+            action: DuplicateAction::Drop,
+        };
+        let (model, summary) = dedup(&near);
+
+        // the slightly off (0,0)->1 goes too
+        assert_eq!(summary.duplicates, 4);
+        assert_eq!(summary.after, 6);
+        assert!(model.weights.iter().all(|w| *w == 1.0));
+    }
+}
@@ -1,8 +1,10 @@
+use rand::{thread_rng, Rng};
+
 pub trait PDF {
     fn evaluate(&self, x: f32) -> f32;
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 // Continuous Uniform Distribution
 pub struct CUD {
     pub a: f32,
@@ -25,7 +27,7 @@ impl PDF for CUD {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct AvgCUD {
     pub cuds: Vec<CUD>,
 }
@@ -37,11 +39,15 @@ impl PDF for AvgCUD {
 }
 
 pub trait Sample {
-    fn sample(&self) -> f32;
+    fn sample(&self) -> f32 {
+        self.sample_with(&mut thread_rng())
+    }
+
+    fn sample_with<R: Rng + ?Sized>(&self, rng: &mut R) -> f32;
 }
 
 impl Sample for CUD {
-    fn sample(&self) -> f32 {
+    fn sample_with<R: Rng + ?Sized>(&self, rng: &mut R) -> f32 {
         // return self.a + self.len() * rand::random::<f32>();
 
         //TODO: Clean this up. I'm trying to fake a smoother distrubution
@@ -53,31 +59,34 @@ impl Sample for CUD {
         let center = (self.a + self.b) / 2.0;
         let half_width = self.len() / 2.0;
 
-        let unit_spread = (rand::random::<f32>() - 0.5) * 2.0;
+        let unit_spread = (rng.gen::<f32>() - 0.5) * 2.0;
 
         center + half_width * (unit_spread * unit_spread * unit_spread)
     }
 }
 
 impl Sample for AvgCUD {
-    fn sample(&self) -> f32 {
+    fn sample_with<R: Rng + ?Sized>(&self, rng: &mut R) -> f32 {
         let total = self.cuds.iter().map(CUD::len).sum::<f32>();
 
-        let cutoff = rand::random::<f32>() * total;
+        let cutoff = rng.gen::<f32>() * total;
         let mut bar = 0.0;
         for cud in &self.cuds {
             bar += cud.len();
             if bar > cutoff {
-                return cud.sample();
+                return cud.sample_with(rng);
             }
         }
 
-        self.cuds.last().unwrap().sample()
+        self.cuds.last().unwrap().sample_with(rng)
     }
 }
 
+#[derive(Default)]
 pub struct WeightedAvgCUD<'a> {
     weighted_cuds: Vec<(&'a CUD, f32)>,
+    // one per AvgCUD blended, normalized
+    weights: Vec<f32>,
 }
 
 impl<'a> WeightedAvgCUD<'a> {
@@ -87,32 +96,61 @@ impl<'a> WeightedAvgCUD<'a> {
         let weighted_cuds = avg_cuds
             .iter()
             .zip(weights)
-            .map(|(&avg_cud, w)| avg_cud.cuds.iter().map(|cud| (cud, *w)))
-            .flatten()
+            .flat_map(|(&avg_cud, w)| avg_cud.cuds.iter().map(|cud| (cud, *w)))
             .collect();
 
-        WeightedAvgCUD { weighted_cuds }
+        let total: f32 = weights.iter().sum();
+        let weights = weights.iter().map(|w| w / total).collect();
+
+        WeightedAvgCUD {
+            weighted_cuds,
+            weights,
+        }
+    }
+
+    // Like from_weighted_avgcuds, but reusing this one's buffer and normalizing the weights as it goes
+    pub fn refill_weighted_avgcuds<I>(&mut self, weighted_avg_cuds: I)
+    where
+        I: IntoIterator<Item = (f32, &'a AvgCUD)>,
+    {
+        self.weighted_cuds.clear();
+        self.weights.clear();
+
+        let mut total = 0.0;
+        for (w, avg_cud) in weighted_avg_cuds {
+            total += w;
+            self.weights.push(w);
+            self.weighted_cuds
+                .extend(avg_cud.cuds.iter().map(|cud| (cud, w)));
+        }
+
+        self.weighted_cuds.iter_mut().for_each(|(_, w)| *w /= total);
+        self.weights.iter_mut().for_each(|w| *w /= total);
+    }
+
+    pub fn weights(&self) -> &[f32] {
+        &self.weights
     }
 }
 
 impl Sample for WeightedAvgCUD<'_> {
-    fn sample(&self) -> f32 {
+    fn sample_with<R: Rng + ?Sized>(&self, rng: &mut R) -> f32 {
         let total = self
             .weighted_cuds
             .iter()
             .map(|(cud, w)| cud.len() * w)
             .sum::<f32>();
 
-        let cutoff = rand::random::<f32>() * total;
+        let cutoff = rng.gen::<f32>() * total;
         let mut bar = 0.0;
         for (cud, w) in &self.weighted_cuds {
             bar += cud.len() * w;
             if bar > cutoff {
-                return cud.sample();
+                return cud.sample_with(rng);
             }
         }
 
-        self.weighted_cuds.last().unwrap().0.sample()
+        self.weighted_cuds.last().unwrap().0.sample_with(rng)
     }
 }
 
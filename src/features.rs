@@ -0,0 +1,145 @@
+use std::ops::Range;
+
+use crate::bmd::{PositionState, SpikeDist, BMD};
+
+// Computes a state from the most recent frames of a series, newest frame last.
+// Unlike FromHistory the extractor itself can carry settings like the frame time.
+pub trait FeatureExtractor<const OUT: usize> {
+    type State: PositionState;
+
+    // how many frames of history extract needs
+    fn span(&self) -> usize;
+
+    fn extract(&self, history: &[[f32; OUT]]) -> Self::State;
+}
+
+// A flat vector of derived features
+#[derive(Debug, Clone, PartialEq)]
+pub struct Features(pub Vec<f32>);
+
+impl PositionState for Features {
+    fn similarity(&self, other: &Self) -> f32 {
+        let distance = self
+            .0
+            .iter()
+            .zip(other.0.iter())
+            .map(|(u, v)| (u - v) * (u - v))
+            .sum::<f32>()
+            .sqrt();
+
+        const WIDENESS: f32 = 0.1;
+
+        f32::exp(-distance / WIDENESS)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Feature {
+    // the newest frame as is
+    Pose,
+    // finite differences over the last two or three frames, per channel
+    Velocity,
+    Acceleration,
+    // magnitude of the velocity of the location channels
+    Speed,
+    // magnitude of the velocity of the rotation channels, treats them as small euler steps
+    AngularSpeed,
+}
+
+impl Feature {
+    fn span(&self) -> usize {
+        match self {
+            Feature::Pose => 1,
+            Feature::Velocity | Feature::Speed | Feature::AngularSpeed => 2,
+            Feature::Acceleration => 3,
+        }
+    }
+}
+
+// Extracts a list of features, each multiplied by its scale so they can be balanced against each other
+#[derive(Debug, Clone)]
+pub struct FeatureSet {
+    pub features: Vec<(Feature, f32)>,
+    pub frametime: f32,
+    pub location: Range<usize>,
+    pub rotation: Range<usize>,
+}
+
+impl FeatureSet {
+    // the sword data is 3 location channels followed by 3 rotation channels
+    pub fn sword(features: Vec<(Feature, f32)>, frametime: f32) -> Self {
+        FeatureSet {
+            features,
+            frametime,
+            location: 0..3,
+            rotation: 3..6,
+        }
+    }
+}
+
+impl<const OUT: usize> FeatureExtractor<OUT> for FeatureSet {
+    type State = Features;
+
+    fn span(&self) -> usize {
+        self.features.iter().map(|(f, _)| f.span()).max().unwrap_or(1)
+    }
+
+    fn extract(&self, history: &[[f32; OUT]]) -> Features {
+        assert!(
+            history.len() >= FeatureExtractor::<OUT>::span(self),
+            "not enough history for features"
+        );
+
+        let n = history.len();
+        let velocity = |i: usize| (history[n - 1][i] - history[n - 2][i]) / self.frametime;
+        let magnitude = |channels: &Range<usize>| {
+            channels
+                .clone()
+                .map(|i| velocity(i) * velocity(i))
+                .sum::<f32>()
+                .sqrt()
+        };
+
+        let mut out = Vec::new();
+        for (feature, scale) in &self.features {
+            match feature {
+                Feature::Pose => out.extend(history[n - 1].iter().map(|x| x * scale)),
+                Feature::Velocity => out.extend((0..OUT).map(|i| velocity(i) * scale)),
+                Feature::Acceleration => out.extend((0..OUT).map(|i| {
+                    (history[n - 1][i] - 2.0 * history[n - 2][i] + history[n - 3][i])
+                        / (self.frametime * self.frametime)
+                        * scale
+                })),
+                Feature::Speed => out.push(magnitude(&self.location) * scale),
+                Feature::AngularSpeed => out.push(magnitude(&self.rotation) * scale),
+            }
+        }
+
+        Features(out)
+    }
+}
+
+impl<Pos: PositionState, const OUT: usize> BMD<Pos, SpikeDist<[f32; OUT]>> {
+    // like from_series, but matching on extracted features while still predicting raw frames
+    pub fn from_series_with<E>(extractor: &E, series: &[[f32; OUT]], side_len: f32) -> Self
+    where
+        E: FeatureExtractor<OUT, State = Pos>,
+    {
+        let span = extractor.span();
+
+        let distributions = series
+            .windows(span + 1)
+            .map(|window| {
+                (
+                    extractor.extract(&window[..span]),
+                    SpikeDist {
+                        pos: window[span],
+                        side_len,
+                    },
+                )
+            })
+            .collect();
+
+        BMD { distributions }
+    }
+}
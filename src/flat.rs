@@ -0,0 +1,295 @@
+use crate::bmd::{BlendedDist, PositionState, BMD};
+
+// A state that is a fixed length vector compared by weighted squared distance,
+// so similarities can be worked out in bulk instead of one pair at a time.
+// kernel(weighted squared distance) has to match what similarity gives.
+pub trait FlatState: PositionState {
+    const DIM: usize;
+
+    fn write_coords(&self, out: &mut [f32]);
+
+    fn dim_weight(dim: usize) -> f32;
+
+    fn kernel(sq_distance: f32) -> f32;
+}
+
+// how many stored states get their distances worked out together, wide enough for 8 f32 lanes
+const LANES: usize = 8;
+
+// The stored states of a BMD laid out as structure of arrays, one column per dimension,
+// padded to a multiple of LANES so the distance loop auto vectorizes.
+// It is a copy, so it has to be rebuilt if the BMD's distributions change.
+#[derive(Debug, Clone)]
+pub struct FlatIndex {
+    dim: usize,
+    len: usize,
+    padded: usize,
+    columns: Vec<f32>,
+    weights: Vec<f32>,
+    pub threads: usize,
+    // only split across threads once there are at least this many stored states
+    pub parallel_above: usize,
+}
+
+impl FlatIndex {
+    pub fn new<Pos: FlatState, Dist>(bmd: &BMD<Pos, Dist>) -> Self {
+        let len = bmd.distributions.len();
+        let padded = len.div_ceil(LANES) * LANES;
+
+        let mut columns = vec![0.0; Pos::DIM * padded];
+        let mut coords = vec![0.0; Pos::DIM];
+        for (i, (pos, _)) in bmd.distributions.iter().enumerate() {
+            pos.write_coords(&mut coords);
+            for (d, c) in coords.iter().enumerate() {
+                columns[d * padded + i] = *c;
+            }
+        }
+
+        FlatIndex {
+            dim: Pos::DIM,
+            len,
+            padded,
+            columns,
+            weights: (0..Pos::DIM).map(Pos::dim_weight).collect(),
+            threads: std::thread::available_parallelism().map_or(1, |n| n.get()),
+            parallel_above: 16_384,
+        }
+    }
+
+    // adds one more stored state to the end, to match BMD::push
+    pub fn push<Pos: FlatState>(&mut self, pos: &Pos) {
+        assert_eq!(Pos::DIM, self.dim, "index was built for a different state");
+
+        let mut coords = vec![0.0; self.dim];
+        pos.write_coords(&mut coords);
+        self.push_coords(&coords);
+    }
+
+    pub(crate) fn push_coords(&mut self, coords: &[f32]) {
+        if self.len == self.padded {
+            // out of padding, lay the columns out again with twice the room
+            let padded = (self.padded * 2).max(LANES);
+            let mut columns = vec![0.0; self.dim * padded];
+            for d in 0..self.dim {
+                columns[d * padded..][..self.len]
+                    .copy_from_slice(&self.columns[d * self.padded..][..self.len]);
+            }
+            self.columns = columns;
+            self.padded = padded;
+        }
+
+        for (d, c) in coords.iter().enumerate() {
+            self.columns[d * self.padded + self.len] = *c;
+        }
+        self.len += 1;
+    }
+
+    // drops the first `count` stored states, to match BMD::remove_oldest
+    pub fn remove_oldest(&mut self, count: usize) {
+        let count = count.min(self.len);
+        for d in 0..self.dim {
+            let start = d * self.padded;
+            self.columns
+                .copy_within(start + count..start + self.len, start);
+        }
+        self.len -= count;
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn dim(&self) -> usize {
+        self.dim
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // kernel of the weighted squared distances for entries start.., out is a multiple of LANES long
+    fn similarities_from(
+        &self,
+        query: &[f32],
+        kernel: fn(f32) -> f32,
+        start: usize,
+        out: &mut [f32],
+    ) {
+        for (c, chunk) in out.chunks_exact_mut(LANES).enumerate() {
+            let offset = start + c * LANES;
+
+            let mut acc = [0.0_f32; LANES];
+            for (d, (q, w)) in query.iter().zip(&self.weights).enumerate() {
+                let column = &self.columns[d * self.padded + offset..][..LANES];
+                for (a, x) in acc.iter_mut().zip(column) {
+                    let diff = x - q;
+                    *a += w * diff * diff;
+                }
+            }
+
+            for (o, a) in chunk.iter_mut().zip(acc) {
+                *o = kernel(a);
+            }
+        }
+    }
+
+    // the similarity of every stored state to eval_pos, in the same order as the BMD
+    pub fn similarities<Pos: FlatState>(&self, eval_pos: &Pos) -> Vec<f32> {
+        assert_eq!(Pos::DIM, self.dim, "index was built for a different state");
+
+        let mut query = vec![0.0; self.dim];
+        eval_pos.write_coords(&mut query);
+
+        self.similarities_of(&query, Pos::kernel)
+    }
+
+    pub(crate) fn similarities_of(&self, query: &[f32], kernel: fn(f32) -> f32) -> Vec<f32> {
+        let mut out = vec![0.0; self.padded];
+        let threads = self.threads.max(1);
+
+        if self.len < self.parallel_above || threads == 1 {
+            self.similarities_from(query, kernel, 0, &mut out);
+        } else {
+            let per_thread = self.padded.div_ceil(threads).div_ceil(LANES) * LANES;
+            std::thread::scope(|scope| {
+                for (t, part) in out.chunks_mut(per_thread).enumerate() {
+                    scope
+                        .spawn(move || self.similarities_from(query, kernel, t * per_thread, part));
+                }
+            });
+        }
+
+        out.truncate(self.len);
+        out
+    }
+}
+
+impl<'a, Pos: FlatState, Dist: 'a> BMD<Pos, Dist> {
+    // same as interpolate, but with similarities from a FlatIndex built from this BMD
+    pub fn interpolate_with<T>(&'a self, index: &FlatIndex, eval_pos: Pos) -> T
+    where
+        T: BlendedDist<'a, &'a Dist> + 'a,
+    {
+        assert_eq!(
+            index.len(),
+            self.distributions.len(),
+            "index is out of date"
+        );
+
+        let weighted_dists = index
+            .similarities(&eval_pos)
+            .into_iter()
+            .zip(self.distributions.iter().map(|(_, dist)| dist));
+
+        T::from(weighted_dists)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        bmd::SpikeDist,
+        lookback::{Dilated, FromHistory, Lookback},
+    };
+
+    fn series(len: usize) -> Vec<[f32; 3]> {
+        (0..len)
+            .map(|t| {
+                let t = t as f32;
+                [0.02 * (0.3 * t).sin(), 0.02 * (0.17 * t).cos(), 0.001 * t]
+            })
+            .collect()
+    }
+
+    fn assert_matches_similarity<Pos: FlatState>(
+        index: &FlatIndex,
+        bmd: &BMD<Pos, SpikeDist<[f32; 3]>>,
+        query: &Pos,
+    ) {
+        let expected: Vec<f32> = bmd
+            .distributions
+            .iter()
+            .map(|(pos, _)| pos.similarity(query))
+            .collect();
+        let got = index.similarities(query);
+
+        assert_eq!(got.len(), expected.len());
+        for (i, (g, e)) in got.iter().zip(&expected).enumerate() {
+            assert!(
+                (g - e).abs() <= 1e-5,
+                "pair {i}: index gave {g}, similarity gave {e}"
+            );
+        }
+        // the queries come from the series, so something has to be similar
+        assert!(expected.iter().any(|e| *e > 0.1));
+    }
+
+    fn check_parity<Pos: FlatState + FromHistory<3>>() {
+        let series = series(60);
+        let bmd: BMD<Pos, _> = BMD::from_series(&series, 0.0);
+        let query = Pos::from_history(&series[..Pos::SPAN + 7]);
+
+        let mut index = FlatIndex::new(&bmd);
+        assert_matches_similarity(&index, &bmd, &query);
+
+        // the threaded path splits the same columns up differently
+        index.parallel_above = 0;
+        index.threads = 3;
+        assert_matches_similarity(&index, &bmd, &query);
+    }
+
+    #[test]
+    fn lookback_index_matches_similarity() {
+        check_parity::<Lookback<4, 3>>();
+    }
+
+    #[test]
+    fn dilated_index_matches_similarity() {
+        check_parity::<Dilated<4, 3>>();
+    }
+
+    // pushing past the padding lays the columns out again, removing shifts them down
+    fn check_push_and_remove<Pos: FlatState + FromHistory<3>>() {
+        let series = series(120);
+        let windows: Vec<Pos> = series.windows(Pos::SPAN).map(Pos::from_history).collect();
+        let query = Pos::from_history(&series[30..30 + Pos::SPAN]);
+
+        let mut bmd: BMD<Pos, SpikeDist<[f32; 3]>> =
+            BMD::from_series(&series[..Pos::SPAN + 3], 0.0);
+        let mut index = FlatIndex::new(&bmd);
+
+        for (i, pos) in windows.into_iter().enumerate().skip(3) {
+            index.push(&pos);
+            bmd.push(
+                pos,
+                SpikeDist {
+                    pos: series[i],
+                    side_len: 0.0,
+                },
+            );
+
+            if i % 10 == 0 {
+                index.remove_oldest(4);
+                bmd.remove_oldest(4);
+            }
+            if i % 17 == 0 {
+                assert_matches_similarity(&index, &bmd, &query);
+            }
+        }
+
+        assert_matches_similarity(&index, &bmd, &query);
+        let fresh = FlatIndex::new(&bmd);
+        assert_eq!(index.similarities(&query), fresh.similarities(&query));
+    }
+
+    #[test]
+    fn lookback_index_survives_push_and_remove() {
+        check_push_and_remove::<Lookback<4, 3>>();
+    }
+
+    #[test]
+    fn dilated_index_survives_push_and_remove() {
+        check_push_and_remove::<Dilated<4, 3>>();
+    }
+}
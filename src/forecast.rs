@@ -0,0 +1,135 @@
+use crate::{
+    batch::Batch,
+    bmd::{BlendedDist, Interpolate},
+};
+
+// Something a model outputs each frame, seen as a handful of channels
+pub trait Frame: Copy {
+    fn channels(&self) -> &[f32];
+}
+
+impl Frame for f32 {
+    fn channels(&self) -> &[f32] {
+        std::slice::from_ref(self)
+    }
+}
+
+impl<const N: usize> Frame for [f32; N] {
+    fn channels(&self) -> &[f32] {
+        self
+    }
+}
+
+// Per step, per channel summaries of many rollouts.
+// Indexed [step][channel], bands are one per quantile in the same order as `quantiles`.
+#[derive(Debug, Clone)]
+pub struct Forecast {
+    pub quantiles: Vec<f32>,
+    pub mean: Vec<Vec<f32>>,
+    pub bands: Vec<Vec<Vec<f32>>>,
+}
+
+// Runs `rollouts` independent rollouts of `horizon` steps from the same history, as a Batch.
+#[derive(Debug, Clone)]
+pub struct Forecaster {
+    pub rollouts: usize,
+    pub horizon: usize,
+    pub quantiles: Vec<f32>,
+    pub seed: u64,
+    pub threads: usize,
+}
+
+impl Forecaster {
+    pub fn new(rollouts: usize, horizon: usize, seed: u64) -> Self {
+        Forecaster {
+            rollouts,
+            horizon,
+            quantiles: vec![0.05, 0.25, 0.5, 0.75, 0.95],
+            seed,
+            threads: std::thread::available_parallelism().map_or(1, |n| n.get()),
+        }
+    }
+
+    // `make_state` builds the position state from the frames so far, newest last
+    pub fn run<'a, M, T, Out, F>(&self, model: &'a M, history: &[Out], make_state: F) -> Forecast
+    where
+        M: Interpolate + Sync,
+        M::Dist: 'a,
+        T: BlendedDist<'a, &'a M::Dist, OutputState = Out> + Default + 'a,
+        Out: Frame + Send + Sync,
+        F: Fn(&[Out]) -> M::Pos + Sync,
+    {
+        let batch = Batch {
+            rollouts: self.rollouts,
+            seed: self.seed,
+            threads: self.threads,
+        };
+        let rollouts = batch.generate::<M, T, Out, F>(model, history, self.horizon, make_state);
+
+        self.summarize(&rollouts)
+    }
+
+    fn summarize<Out: Frame>(&self, rollouts: &[Vec<Out>]) -> Forecast {
+        let steps = rollouts.iter().map(Vec::len).min().unwrap_or(0);
+        let channels = rollouts
+            .first()
+            .and_then(|r| r.first())
+            .map_or(0, |f| f.channels().len());
+
+        let mut mean = vec![vec![0.0; channels]; steps];
+        let mut bands = vec![vec![vec![0.0; channels]; steps]; self.quantiles.len()];
+
+        let mut values = Vec::with_capacity(rollouts.len());
+        for step in 0..steps {
+            for channel in 0..channels {
+                values.clear();
+                values.extend(rollouts.iter().map(|r| r[step].channels()[channel]));
+                values.sort_by(f32::total_cmp);
+
+                mean[step][channel] = values.iter().sum::<f32>() / values.len() as f32;
+                for (band, &q) in bands.iter_mut().zip(&self.quantiles) {
+                    band[step][channel] = quantile(&values, q);
+                }
+            }
+        }
+
+        Forecast {
+            quantiles: self.quantiles.clone(),
+            mean,
+            bands,
+        }
+    }
+}
+
+// linear interpolation between the closest ranks of already sorted values
+fn quantile(sorted: &[f32], q: f32) -> f32 {
+    let pos = q.clamp(0.0, 1.0) * (sorted.len() - 1) as f32;
+    let lower = pos.floor() as usize;
+    let upper = pos.ceil() as usize;
+    let t = pos - lower as f32;
+
+    sorted[lower] * (1.0 - t) + sorted[upper] * t
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quantile_interpolates_between_ranks() {
+        let sorted = [1.0, 2.0, 3.0, 4.0, 5.0];
+
+        assert_eq!(quantile(&sorted, 0.0), 1.0);
+        assert_eq!(quantile(&sorted, 0.5), 3.0);
+        assert_eq!(quantile(&sorted, 1.0), 5.0);
+        assert_eq!(quantile(&sorted, 0.125), 1.5);
+        // out of range quantiles are clamped
+        assert_eq!(quantile(&sorted, -1.0), 1.0);
+        assert_eq!(quantile(&sorted, 2.0), 5.0);
+    }
+
+    #[test]
+    fn quantile_of_one_value_is_that_value() {
+        assert_eq!(quantile(&[7.0], 0.3), 7.0);
+    }
+}
@@ -0,0 +1,91 @@
+use crate::{
+    bmd::{BlendedDist, Interpolate},
+    lookback::FromHistory,
+};
+
+// Fills a gap in a recording from both sides.
+// `forward` predicts the next frame from the ones before it, `backward` is trained with
+// Direction::Backward so it predicts the previous frame from the ones after it.
+#[derive(Debug)]
+pub struct Infill<'m, M> {
+    pub forward: &'m M,
+    pub backward: &'m M,
+    // how many rollouts to try from each side
+    pub candidates: usize,
+}
+
+// how much the backward rollout counts at frame t of the gap, easing from 0 to 1
+fn crossfade(t: usize, gap: usize) -> f32 {
+    let x = (t + 1) as f32 / (gap + 1) as f32;
+    x * x * (3.0 - 2.0 * x)
+}
+
+impl<'m, M: Interpolate> Infill<'m, M> {
+    pub fn new(forward: &'m M, backward: &'m M) -> Self {
+        Infill {
+            forward,
+            backward,
+            candidates: 16,
+        }
+    }
+
+    // Generates the `gap` frames between `before` and `after`.
+    // Rollouts are made forward from `before` and backward from `after`, the pair that agrees
+    // the most through the middle of the gap is picked and crossfaded from one to the other.
+    pub fn fill<T, const OUT: usize>(
+        &self,
+        before: &[[f32; OUT]],
+        after: &[[f32; OUT]],
+        gap: usize,
+    ) -> Vec<[f32; OUT]>
+    where
+        M::Pos: FromHistory<OUT>,
+        M::Dist: 'm,
+        T: BlendedDist<'m, &'m M::Dist, OutputState = [f32; OUT]> + Default + 'm,
+    {
+        let forwards: Vec<_> = (0..self.candidates)
+            .map(|_| self.forward.generate::<T, OUT>(before, gap))
+            .collect();
+        let backwards: Vec<Vec<_>> = (0..self.candidates)
+            .map(|_| self.backward.generate_backward::<T, OUT>(after, gap))
+            .collect();
+
+        // disagreement counts most in the middle, where both sides contribute
+        let mismatch = |f: &[[f32; OUT]], b: &[[f32; OUT]]| {
+            f.iter()
+                .zip(b)
+                .enumerate()
+                .map(|(t, (x, y))| {
+                    let a = crossfade(t, gap);
+                    let d = x.iter().zip(y).map(|(u, v)| (u - v) * (u - v)).sum::<f32>();
+                    a * (1.0 - a) * d
+                })
+                .sum::<f32>()
+        };
+
+        let mut best = (f32::INFINITY, 0, 0);
+        for (i, f) in forwards.iter().enumerate() {
+            for (j, b) in backwards.iter().enumerate() {
+                let m = mismatch(f, b);
+                if m < best.0 {
+                    best = (m, i, j);
+                }
+            }
+        }
+
+        let (_, i, j) = best;
+        forwards[i]
+            .iter()
+            .zip(&backwards[j])
+            .enumerate()
+            .map(|(t, (f, b))| {
+                let a = crossfade(t, gap);
+                let mut frame = [0.0; OUT];
+                for k in 0..OUT {
+                    frame[k] = (1.0 - a) * f[k] + a * b[k];
+                }
+                frame
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,324 @@
+use rand::Rng;
+
+use crate::{
+    bmd::{effective_sample_size, BlendedDist, Interpolate},
+    lookback::FromHistory,
+};
+
+// Generated frame `frame` (0 is the first new frame) should be within `tolerance` of `pose`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Keyframe<const OUT: usize> {
+    pub frame: usize,
+    pub pose: [f32; OUT],
+    pub tolerance: f32,
+}
+
+// Sequential Monte Carlo over rollouts of a BMD.
+// Particles are proposed from the model itself, so its transition densities cancel out of the
+// importance weights and only the keyframe potentials are left to reweight by.
+#[derive(Debug, Clone, Copy)]
+pub struct KeyframeSampler {
+    pub particles: usize,
+    // how far a rollout is expected to wander per frame, used to judge if a keyframe is still reachable
+    pub spread: f32,
+    // resample once the effective sample size drops below this fraction of the particles
+    pub resample_below: f32,
+}
+
+#[derive(Debug, Clone)]
+pub struct KeyframeTrajectory<const OUT: usize> {
+    pub frames: Vec<[f32; OUT]>,
+    pub log_weight: f32,
+    // the furthest any keyframe was missed by, compare against the tolerances
+    pub max_error: f32,
+    pub hit: bool,
+}
+
+fn distance_sq<const OUT: usize>(a: &[f32; OUT], b: &[f32; OUT]) -> f32 {
+    a.iter().zip(b).map(|(u, v)| (u - v) * (u - v)).sum()
+}
+
+impl KeyframeSampler {
+    pub fn new(particles: usize, spread: f32) -> Self {
+        assert!(particles > 0, "need at least one particle");
+
+        KeyframeSampler {
+            particles,
+            spread,
+            resample_below: 0.5,
+        }
+    }
+
+    // log of how plausible it still is to reach `key` from `x` at frame `t`,
+    // a random walk bridge that tightens to the tolerance on the keyframe itself
+    fn log_potential<const OUT: usize>(
+        &self,
+        key: &Keyframe<OUT>,
+        x: &[f32; OUT],
+        t: usize,
+    ) -> f32 {
+        let steps = key.frame.saturating_sub(t) as f32;
+        let variance = key.tolerance * key.tolerance + self.spread * self.spread * steps;
+
+        -distance_sq(x, &key.pose) / (2.0 * variance)
+    }
+
+    // Runs every particle for `length` frames after `history` and returns them all,
+    // most heavily weighted first. Every keyframe has to fall inside those frames.
+    pub fn run<'a, M, T, R, const OUT: usize>(
+        &self,
+        model: &'a M,
+        history: &[[f32; OUT]],
+        keyframes: &[Keyframe<OUT>],
+        length: usize,
+        rng: &mut R,
+    ) -> Vec<KeyframeTrajectory<OUT>>
+    where
+        M: Interpolate,
+        M::Pos: FromHistory<OUT>,
+        M::Dist: 'a,
+        T: BlendedDist<'a, &'a M::Dist, OutputState = [f32; OUT]> + 'a,
+        R: Rng + ?Sized,
+    {
+        assert!(self.particles > 0, "need at least one particle");
+        assert!(
+            keyframes.iter().all(|k| k.frame < length),
+            "keyframes have to be before frame {length}"
+        );
+
+        let mut keyframes = keyframes.to_vec();
+        keyframes.sort_by_key(|k| k.frame);
+
+        let mut particles = vec![history.to_vec(); self.particles];
+        let mut log_weights = vec![0.0; self.particles];
+        let mut last_potentials = vec![0.0; self.particles];
+
+        for t in 0..length {
+            let target = keyframes.iter().find(|k| k.frame >= t);
+
+            for ((frames, log_w), last) in particles
+                .iter_mut()
+                .zip(&mut log_weights)
+                .zip(&mut last_potentials)
+            {
+                let new = model
+                    .interpolate::<T>(M::Pos::from_history(frames))
+                    .sample_with(rng);
+                frames.push(new);
+
+                if let Some(key) = target {
+                    // potentials telescope, so only the change since last frame goes into the weight
+                    let potential = self.log_potential(key, &new, t);
+                    *log_w += potential - *last;
+                    // a keyframe's final potential stays baked into the weight
+                    *last = if key.frame == t { 0.0 } else { potential };
+                }
+            }
+
+            // never on the last frame, so the final weights can still rank the trajectories
+            let weights = normalized(&log_weights);
+            if t + 1 < length
+                && effective_sample_size(&weights) < self.resample_below * self.particles as f32
+            {
+                let picks = systematic_resample(&weights, self.particles, rng);
+
+                particles = picks.iter().map(|&i| particles[i].clone()).collect();
+                last_potentials = picks.iter().map(|&i| last_potentials[i]).collect();
+                log_weights = vec![0.0; self.particles];
+            }
+        }
+
+        let mut trajectories: Vec<_> = particles
+            .into_iter()
+            .zip(log_weights)
+            .map(|(mut frames, log_weight)| {
+                let frames = frames.split_off(history.len());
+
+                let mut max_error = 0.0_f32;
+                let mut hit = true;
+                for key in &keyframes {
+                    let error = distance_sq(&frames[key.frame], &key.pose).sqrt();
+                    max_error = max_error.max(error);
+                    hit &= error <= key.tolerance;
+                }
+
+                KeyframeTrajectory {
+                    frames,
+                    log_weight,
+                    max_error,
+                    hit,
+                }
+            })
+            .collect();
+
+        trajectories.sort_by(|a, b| b.log_weight.total_cmp(&a.log_weight));
+        trajectories
+    }
+
+    // The most heavily weighted trajectory that hits every keyframe. Weights only count from the
+    // last resample, so they can't be trusted to have seen earlier misses. If none of them hit,
+    // the one that missed by the least.
+    pub fn best<'a, M, T, R, const OUT: usize>(
+        &self,
+        model: &'a M,
+        history: &[[f32; OUT]],
+        keyframes: &[Keyframe<OUT>],
+        length: usize,
+        rng: &mut R,
+    ) -> KeyframeTrajectory<OUT>
+    where
+        M: Interpolate,
+        M::Pos: FromHistory<OUT>,
+        M::Dist: 'a,
+        T: BlendedDist<'a, &'a M::Dist, OutputState = [f32; OUT]> + 'a,
+        R: Rng + ?Sized,
+    {
+        let mut trajectories = self.run::<M, T, R, OUT>(model, history, keyframes, length, rng);
+
+        // already sorted by weight, so the first hit is the heaviest one
+        let pick = trajectories.iter().position(|t| t.hit).unwrap_or_else(|| {
+            (0..trajectories.len())
+                .min_by(|&a, &b| {
+                    trajectories[a]
+                        .max_error
+                        .total_cmp(&trajectories[b].max_error)
+                })
+                .unwrap()
+        });
+
+        trajectories.swap_remove(pick)
+    }
+
+    // a trajectory drawn in proportion to the final weights
+    pub fn sample<'a, M, T, R, const OUT: usize>(
+        &self,
+        model: &'a M,
+        history: &[[f32; OUT]],
+        keyframes: &[Keyframe<OUT>],
+        length: usize,
+        rng: &mut R,
+    ) -> KeyframeTrajectory<OUT>
+    where
+        M: Interpolate,
+        M::Pos: FromHistory<OUT>,
+        M::Dist: 'a,
+        T: BlendedDist<'a, &'a M::Dist, OutputState = [f32; OUT]> + 'a,
+        R: Rng + ?Sized,
+    {
+        let mut trajectories = self.run::<M, T, R, OUT>(model, history, keyframes, length, rng);
+
+        let log_weights: Vec<f32> = trajectories.iter().map(|t| t.log_weight).collect();
+        let pick = systematic_resample(&normalized(&log_weights), 1, rng)[0];
+
+        trajectories.swap_remove(pick)
+    }
+}
+
+// exp of the log weights, shifted so the biggest one is 1.0
+fn normalized(log_weights: &[f32]) -> Vec<f32> {
+    let max = log_weights
+        .iter()
+        .copied()
+        .fold(f32::NEG_INFINITY, f32::max);
+
+    log_weights.iter().map(|lw| (lw - max).exp()).collect()
+}
+
+// indices of the picked particles, one random offset shared by evenly spaced pointers
+fn systematic_resample<R: Rng + ?Sized>(weights: &[f32], count: usize, rng: &mut R) -> Vec<usize> {
+    let total: f32 = weights.iter().sum();
+    let step = total / count as f32;
+    let mut pointer = rng.gen::<f32>() * step;
+
+    let mut picks = Vec::with_capacity(count);
+    let mut bar = 0.0;
+    let mut i = 0;
+    for _ in 0..count {
+        while i + 1 < weights.len() && bar + weights[i] < pointer {
+            bar += weights[i];
+            i += 1;
+        }
+        picks.push(i);
+        pointer += step;
+    }
+
+    picks
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+    use crate::{
+        bmd::{SpikeDist, WeightedSpikes, BMD},
+        lookback::Lookback,
+    };
+
+    // from 0 it goes to 1 or -1 evenly, then stays wherever it went
+    fn fork() -> BMD<Lookback<1, 1>, SpikeDist<[f32; 1]>> {
+        let pair = |from: f32, to: f32| {
+            (
+                Lookback([[from]]),
+                SpikeDist {
+                    pos: [to],
+                    side_len: 0.0,
+                },
+            )
+        };
+
+        BMD {
+            distributions: vec![
+                pair(0.0, 1.0),
+                pair(0.0, -1.0),
+                pair(1.0, 1.0),
+                pair(-1.0, -1.0),
+            ],
+        }
+    }
+
+    #[test]
+    fn systematic_resample_follows_the_weights() {
+        let picks = systematic_resample(&[0.0, 1.0, 0.0, 3.0], 4, &mut StdRng::seed_from_u64(1));
+        assert_eq!(picks, [1, 3, 3, 3]);
+    }
+
+    #[test]
+    fn best_takes_the_branch_the_keyframe_asks_for() {
+        let keyframes = [Keyframe {
+            frame: 2,
+            pose: [-1.0],
+            tolerance: 0.1,
+        }];
+
+        let best = KeyframeSampler::new(50, 0.5).best::<_, WeightedSpikes<[f32; 1]>, _, 1>(
+            &fork(),
+            &[[0.0]],
+            &keyframes,
+            3,
+            &mut StdRng::seed_from_u64(2),
+        );
+
+        assert!(best.hit);
+        assert_eq!(best.max_error, 0.0);
+        assert_eq!(best.frames, [[-1.0]; 3]);
+    }
+
+    #[test]
+    #[should_panic(expected = "keyframes have to be before frame 3")]
+    fn keyframes_past_the_end_are_rejected() {
+        let keyframes = [Keyframe {
+            frame: 3,
+            pose: [1.0],
+            tolerance: 0.1,
+        }];
+
+        KeyframeSampler::new(10, 0.5).run::<_, WeightedSpikes<[f32; 1]>, _, 1>(
+            &fork(),
+            &[[0.0]],
+            &keyframes,
+            3,
+            &mut StdRng::seed_from_u64(3),
+        );
+    }
+}
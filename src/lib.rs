@@ -1,2 +1,27 @@
-mod bmd;
-mod distribution;
+// lets the PositionState derive refer to this crate by name from inside it too
+extern crate self as blended_markov_distribution;
+
+pub mod batch;
+pub mod beam;
+pub mod bmd;
+pub mod composite;
+pub mod condense;
+pub mod control;
+pub mod data;
+pub mod dataset;
+pub mod dedup;
+pub mod distribution;
+pub mod features;
+pub mod flat;
+pub mod forecast;
+pub mod infill;
+pub mod keyframe;
+pub mod lookback;
+pub mod novelty;
+pub mod online;
+pub mod owned;
+pub mod provenance;
+pub mod successor;
+pub mod temperature;
+pub mod variable_order;
+pub mod weighted;
@@ -0,0 +1,283 @@
+use crate::{
+    bmd::{PositionState, SpikeDist, BMD},
+    flat::FlatState,
+};
+
+// The last LEN frames of an OUT channel series, oldest first
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Lookback<const LEN: usize, const OUT: usize>(pub [[f32; OUT]; LEN]);
+
+// A state built from the most recent frames of a series, newest frame last.
+// SPAN is how many frames of history it needs.
+pub trait FromHistory<const OUT: usize>: Sized {
+    const SPAN: usize;
+
+    fn from_history(history: &[[f32; OUT]]) -> Self;
+}
+
+impl<const LEN: usize, const OUT: usize> FromHistory<OUT> for Lookback<LEN, OUT> {
+    const SPAN: usize = LEN;
+
+    fn from_history(history: &[[f32; OUT]]) -> Self {
+        assert!(history.len() >= LEN, "not enough history for lookback");
+
+        let mut buf = [[0.0; OUT]; LEN];
+        buf.copy_from_slice(&history[history.len() - LEN..]);
+        Lookback(buf)
+    }
+}
+
+// Frames at t-1, t-2, t-4, ... t-2^(LEVELS-1), oldest first.
+// Sees 2^(LEVELS-1) frames back while only storing LEVELS of them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Dilated<const LEVELS: usize, const OUT: usize>(pub [[f32; OUT]; LEVELS]);
+
+// Dilated taps plus the mean of the frames skipped between each tap and the next newer one,
+// so those frames still count as a downsampled summary. The two newest taps sit next to each
+// other and skip nothing, so their means are left at zero and aren't compared.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MultiScale<const LEVELS: usize, const OUT: usize> {
+    pub taps: [[f32; OUT]; LEVELS],
+    pub means: [[f32; OUT]; LEVELS],
+}
+
+const fn dilated_span(levels: usize) -> usize {
+    if levels == 0 {
+        0
+    } else {
+        1 << (levels - 1)
+    }
+}
+
+// offset 1 is the newest frame, level k sits at offset 2^k
+fn dilated_taps<const LEVELS: usize, const OUT: usize>(
+    history: &[[f32; OUT]],
+) -> [[f32; OUT]; LEVELS] {
+    assert!(
+        history.len() >= dilated_span(LEVELS),
+        "not enough history for dilated lookback"
+    );
+
+    let mut taps = [[0.0; OUT]; LEVELS];
+    for (k, tap) in taps.iter_mut().rev().enumerate() {
+        *tap = history[history.len() - (1 << k)];
+    }
+    taps
+}
+
+impl<const LEVELS: usize, const OUT: usize> FromHistory<OUT> for Dilated<LEVELS, OUT> {
+    const SPAN: usize = dilated_span(LEVELS);
+
+    fn from_history(history: &[[f32; OUT]]) -> Self {
+        Dilated(dilated_taps(history))
+    }
+}
+
+impl<const LEVELS: usize, const OUT: usize> FromHistory<OUT> for MultiScale<LEVELS, OUT> {
+    const SPAN: usize = dilated_span(LEVELS);
+
+    fn from_history(history: &[[f32; OUT]]) -> Self {
+        let taps = dilated_taps(history);
+
+        // level k averages offsets 2^(k-1)+1 .. 2^k - 1, the frames between it and level k - 1
+        let mut means = [[0.0; OUT]; LEVELS];
+        for (k, mean) in means.iter_mut().rev().enumerate().skip(2) {
+            let near = (1 << (k - 1)) + 1;
+            let far = (1 << k) - 1;
+            let block = &history[history.len() - far..=history.len() - near];
+
+            for frame in block {
+                for (m, x) in mean.iter_mut().zip(frame) {
+                    *m += x / block.len() as f32;
+                }
+            }
+        }
+
+        MultiScale { taps, means }
+    }
+}
+
+const LOOKBACK_WIDENESS: f32 = 0.1;
+
+// squared distance between two runs of frames, oldest first, with recent frames counting for more
+fn weighted_sq_distance<const OUT: usize>(a: &[[f32; OUT]], b: &[[f32; OUT]]) -> f32 {
+    a.iter()
+        .zip(b)
+        .enumerate()
+        .map(|(i, (u, v))| {
+            let frame = u.iter().zip(v).map(|(a, b)| (a - b) * (a - b)).sum::<f32>();
+            frame * (i as f32 + 1.0)
+        })
+        .sum::<f32>()
+}
+
+// A state that can also be compared using only its most recent frames.
+// `order` is how many frames to look back, from 1 up to MAX_ORDER.
+pub trait VariableOrder: PositionState {
+    const MAX_ORDER: usize;
+
+    fn similarity_at_order(&self, other: &Self, order: usize) -> f32;
+}
+
+impl<const LEN: usize, const OUT: usize> VariableOrder for Lookback<LEN, OUT> {
+    const MAX_ORDER: usize = LEN;
+
+    fn similarity_at_order(&self, other: &Self, order: usize) -> f32 {
+        let order = order.min(LEN);
+
+        // recent frames count for more, same idea as the 12 frame sword state
+        let distance = weighted_sq_distance(&self.0[LEN - order..], &other.0[LEN - order..]).sqrt();
+
+        f32::exp(-distance / LOOKBACK_WIDENESS)
+    }
+}
+
+impl<const LEN: usize, const OUT: usize> PositionState for Lookback<LEN, OUT> {
+    fn similarity(&self, other: &Self) -> f32 {
+        self.similarity_at_order(other, LEN)
+    }
+}
+
+impl<const LEVELS: usize, const OUT: usize> VariableOrder for Dilated<LEVELS, OUT> {
+    const MAX_ORDER: usize = LEVELS;
+
+    // order here is how many of the newest levels are used
+    fn similarity_at_order(&self, other: &Self, order: usize) -> f32 {
+        let order = order.min(LEVELS);
+
+        let distance =
+            weighted_sq_distance(&self.0[LEVELS - order..], &other.0[LEVELS - order..]).sqrt();
+
+        f32::exp(-distance / LOOKBACK_WIDENESS)
+    }
+}
+
+impl<const LEVELS: usize, const OUT: usize> PositionState for Dilated<LEVELS, OUT> {
+    fn similarity(&self, other: &Self) -> f32 {
+        self.similarity_at_order(other, LEVELS)
+    }
+}
+
+impl<const LEVELS: usize, const OUT: usize> VariableOrder for MultiScale<LEVELS, OUT> {
+    const MAX_ORDER: usize = LEVELS;
+
+    fn similarity_at_order(&self, other: &Self, order: usize) -> f32 {
+        let order = order.min(LEVELS);
+        let start = LEVELS - order;
+        // only the levels that skipped any frames have a mean
+        let summarized = LEVELS.saturating_sub(2).max(start);
+
+        let distance = (weighted_sq_distance(&self.taps[start..], &other.taps[start..])
+            + weighted_sq_distance(
+                &self.means[start..summarized],
+                &other.means[start..summarized],
+            ))
+        .sqrt();
+
+        f32::exp(-distance / LOOKBACK_WIDENESS)
+    }
+}
+
+impl<const LEVELS: usize, const OUT: usize> PositionState for MultiScale<LEVELS, OUT> {
+    fn similarity(&self, other: &Self) -> f32 {
+        self.similarity_at_order(other, LEVELS)
+    }
+}
+
+impl<const LEN: usize, const OUT: usize> FlatState for Lookback<LEN, OUT> {
+    const DIM: usize = LEN * OUT;
+
+    fn write_coords(&self, out: &mut [f32]) {
+        for (chunk, frame) in out.chunks_exact_mut(OUT).zip(&self.0) {
+            chunk.copy_from_slice(frame);
+        }
+    }
+
+    // frame i of the lookback counts i + 1 times, like weighted_sq_distance
+    fn dim_weight(dim: usize) -> f32 {
+        (dim / OUT) as f32 + 1.0
+    }
+
+    fn kernel(sq_distance: f32) -> f32 {
+        f32::exp(-sq_distance.sqrt() / LOOKBACK_WIDENESS)
+    }
+}
+
+impl<const LEVELS: usize, const OUT: usize> FlatState for Dilated<LEVELS, OUT> {
+    const DIM: usize = LEVELS * OUT;
+
+    fn write_coords(&self, out: &mut [f32]) {
+        for (chunk, frame) in out.chunks_exact_mut(OUT).zip(&self.0) {
+            chunk.copy_from_slice(frame);
+        }
+    }
+
+    fn dim_weight(dim: usize) -> f32 {
+        (dim / OUT) as f32 + 1.0
+    }
+
+    fn kernel(sq_distance: f32) -> f32 {
+        f32::exp(-sq_distance.sqrt() / LOOKBACK_WIDENESS)
+    }
+}
+
+// Which way through time a model predicts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    // the next frame from the ones before it
+    Forward,
+    // the previous frame from the ones after it, the history is read newest first in real time
+    Backward,
+}
+
+impl<Pos, const OUT: usize> BMD<Pos, SpikeDist<[f32; OUT]>>
+where
+    Pos: PositionState + FromHistory<OUT>,
+{
+    // every SPAN frames of the series predicts the frame after it
+    pub fn from_series(series: &[[f32; OUT]], side_len: f32) -> Self {
+        Self::from_series_in(series, side_len, Direction::Forward)
+    }
+
+    // Backward trains on the time reversed series, so the same states and blending work unchanged
+    pub fn from_series_in(series: &[[f32; OUT]], side_len: f32, direction: Direction) -> Self {
+        let reversed: Vec<_>;
+        let series = match direction {
+            Direction::Forward => series,
+            Direction::Backward => {
+                reversed = series.iter().rev().copied().collect();
+                &reversed
+            }
+        };
+
+        let distributions = series
+            .windows(Pos::SPAN + 1)
+            .map(|window| {
+                (
+                    Pos::from_history(&window[..Pos::SPAN]),
+                    SpikeDist {
+                        pos: window[Pos::SPAN],
+                        side_len,
+                    },
+                )
+            })
+            .collect();
+
+        BMD { distributions }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn multiscale_means_only_cover_skipped_frames() {
+        let history: Vec<[f32; 1]> = (0..8).map(|i| [i as f32]).collect();
+        let state = MultiScale::<4, 1>::from_history(&history);
+
+        assert_eq!(state.taps, [[0.0], [4.0], [6.0], [7.0]]);
+        // frames 1, 2, 3 between taps 0 and 4, frame 5 between taps 4 and 6, nothing after that
+        assert_eq!(state.means, [[2.0], [5.0], [0.0], [0.0]]);
+    }
+}
@@ -1,21 +1,152 @@
-mod bmd;
-mod data;
-mod distribution;
 mod swords;
 
 use std::collections::VecDeque;
 
-use bmd::{Lookback12, RExp, BMD, SpikeDist};
+use blended_markov_distribution::{bmd, data, distribution, forecast::Forecaster};
+
+use bmd::{Lookback12, RExp, BMD};
 use distribution::*;
 
-use crate::bmd::{BlendedDist, WeightedCUDs};
+use crate::bmd::{BlendedDist, Interpolate, WeightedCUDs};
 
 fn main() -> std::io::Result<()> {
+    match std::env::args().nth(1).as_deref() {
+        Some("loc") => {
+            swords::s12_loc::go();
+            Ok(())
+        }
+        Some("varorder") => {
+            swords::s12_varorder::go();
+            Ok(())
+        }
+        Some("posevel") => {
+            swords::s_posevel::go();
+            Ok(())
+        }
+        Some("phase") => {
+            swords::s_phase::go();
+            Ok(())
+        }
+        Some("control") => {
+            let speed = std::env::args()
+                .nth(2)
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(0.1);
+            swords::s_control::go(speed);
+            Ok(())
+        }
+        Some("keyframe") => {
+            swords::s_keyframe::go();
+            Ok(())
+        }
+        Some("infill") => {
+            swords::s_infill::go();
+            Ok(())
+        }
+        Some("leadin") => {
+            swords::s_leadin::go();
+            Ok(())
+        }
+        Some("beam") => {
+            swords::s_beam::go();
+            Ok(())
+        }
+        Some("successor") => {
+            swords::s_successor::go();
+            Ok(())
+        }
+        Some("condense") => {
+            swords::s_condense::go();
+            Ok(())
+        }
+        Some("dedup") => {
+            swords::s_dedup::go();
+            Ok(())
+        }
+        Some("weighted") => {
+            swords::s_weighted::go();
+            Ok(())
+        }
+        Some("clips") => {
+            swords::s_clips::go();
+            Ok(())
+        }
+        Some("explain") => swords::s_clips::explain(),
+        Some("diagnose") => {
+            swords::s_diagnose::go();
+            Ok(())
+        }
+        Some("novelty") => {
+            swords::s_novelty::go();
+            Ok(())
+        }
+        Some("anneal") => {
+            swords::s_novelty::anneal();
+            Ok(())
+        }
+        Some("ball") => bouncing_ball(),
+        Some("ballcast") => ball_forecast(),
+        _ => swords::s12_locrot::go(),
+    }
+}
 
-    swords::s12_locrot::go()?;
-    // swords::s12_loc::go();
+fn ball_frames() -> Vec<f32> {
+    let mut y = 10.0;
+    let mut dy = 0.0;
+    let ddy = -9.8;
+    let frametime = 1.0 / 24.0;
+    let mut frames = Vec::new();
+    for _frame in 0..120 {
+        dy += ddy * frametime;
+        y += dy * frametime;
 
-    return Ok(());
+        if y < 0.0 {
+            y = 0.0;
+            dy = 0.5 * f32::abs(dy);
+        }
+        // println!("{frame}\t{y}");
+        frames.push(y)
+    }
+    frames
+}
+
+// forecast fan for the ball from a few frames in, one line per step: mean then each quantile
+fn ball_forecast() -> std::io::Result<()> {
+    let frames = ball_frames();
+
+    let delta = 0.1;
+    let bmd: BMD<RExp, AvgCUD> = BMD {
+        distributions: frames
+            .windows(2)
+            .map(|window| {
+                (
+                    RExp(window[0]),
+                    AvgCUD {
+                        cuds: vec![CUD {
+                            a: window[1] - delta,
+                            b: window[1] + delta,
+                        }],
+                    },
+                )
+            })
+            .collect(),
+    };
+
+    let forecast = Forecaster::new(500, 60, 1).run::<_, WeightedAvgCUD, _, _>(
+        &bmd,
+        &frames[..10],
+        |history: &[f32]| RExp(*history.last().unwrap()),
+    );
+
+    for (i, mean) in forecast.mean.iter().enumerate() {
+        let bands: Vec<String> = forecast.bands.iter().map(|b| b[i][0].to_string()).collect();
+        println!("{i}\t{}\t{}", mean[0], bands.join("\t"));
+    }
+
+    Ok(())
+}
+
+fn bouncing_ball() -> std::io::Result<()> {
     /*
     let sumcud = AvgCUD {
         cuds: vec![
@@ -51,22 +182,7 @@ fn main() -> std::io::Result<()> {
     return ();
     */
 
-    let mut y = 10.0;
-    let mut dy = 0.0;
-    let ddy = -9.8;
-    let frametime = 1.0 / 24.0;
-    let mut frames = Vec::new();
-    for frame in 0..120 {
-        dy += ddy * frametime;
-        y += dy * frametime;
-
-        if y < 0.0 {
-            y = 0.0;
-            dy = 0.5 * f32::abs(dy);
-        }
-        // println!("{frame}\t{y}");
-        frames.push(y)
-    }
+    let frames = ball_frames();
 
     {
         let mut bmd: BMD<RExp, AvgCUD> = BMD {
@@ -88,7 +204,7 @@ fn main() -> std::io::Result<()> {
         }
 
         let mut last = 9.0;
-        for i in 0..120 {
+        for _i in 0..120 {
             // println!("{i}\t{last}");
             last = distribution::Sample::sample(&bmd.interpolate::<WeightedAvgCUD>(RExp(last)));
         }
@@ -116,9 +232,9 @@ fn main() -> std::io::Result<()> {
 
         dbg!(&bmd12);
 
-        let mut last = frames[0..12].iter().map(|f| *f).collect::<VecDeque<_>>();
+        let _last = frames[0..12].iter().copied().collect::<VecDeque<_>>();
         let mut last = VecDeque::from([9.0; 12]);
-        for i in 0..120 {
+        for _ in 0..120 {
             // println!("{i}\t{}", last.back().unwrap());
             let mut buf = [0.0; 12];
             buf.copy_from_slice(last.make_contiguous());
@@ -149,9 +265,9 @@ fn main() -> std::io::Result<()> {
 
         dbg!(&bmd12);
 
-        let mut last = frames[0..12].iter().map(|f| *f).collect::<VecDeque<_>>();
+        let _last = frames[0..12].iter().copied().collect::<VecDeque<_>>();
         let mut last = VecDeque::from([9.0; 12]);
-        for i in 0..120 {
+        for _ in 0..120 {
             // println!("{i}\t{}", last.back().unwrap());
             let mut buf = [0.0; 12];
             buf.copy_from_slice(last.make_contiguous());
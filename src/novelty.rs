@@ -0,0 +1,168 @@
+use crate::{
+    bmd::{effective_sample_size, Reweight},
+    dataset::{Dataset, Source},
+    temperature::temper,
+};
+
+// how many times min_ess may double the temperature before giving up
+const MAX_HEATINGS: usize = 32;
+
+// Ways to stop a rollout from just replaying the training data, as a Reweight.
+// For a fixed temperature on top, pair it with a temperature::Schedule.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Novelty {
+    // leave out the best matching pair, whose output is the next frame of the training clip it matched
+    pub exclude_successor: bool,
+    // keep doubling the temperature until at least this many neighbours effectively count
+    pub min_ess: f32,
+}
+
+impl Reweight for Novelty {
+    fn reweight(&mut self, _step: usize, _length: usize, weights: &mut [f32]) {
+        if self.exclude_successor {
+            let best = weights
+                .iter()
+                .enumerate()
+                .max_by(|a, b| a.1.total_cmp(b.1))
+                .map(|(i, _)| i);
+
+            // only if something else is left to blend
+            if let Some(best) = best.filter(|_| weights.iter().filter(|w| **w > 0.0).count() > 1) {
+                weights[best] = 0.0;
+            }
+        }
+
+        if effective_sample_size(weights) >= self.min_ess {
+            return;
+        }
+
+        let original = weights.to_vec();
+        let mut temperature = 1.0;
+        for _ in 0..MAX_HEATINGS {
+            temperature *= 2.0;
+            weights.copy_from_slice(&original);
+            temper(weights, temperature);
+
+            if effective_sample_size(weights) >= self.min_ess {
+                break;
+            }
+        }
+    }
+}
+
+// The longest stretch of generated frames that also shows up frame for frame in the training data
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct VerbatimRun {
+    pub length: usize,
+    // index of its first frame in the generated frames
+    pub generated_start: usize,
+    // and where that frame is in the training data
+    pub source: Source,
+}
+
+// Longest common run, frames matching when no channel is further apart than tolerance
+pub fn longest_verbatim_run<const OUT: usize>(
+    generated: &[[f32; OUT]],
+    training: &[[f32; OUT]],
+    tolerance: f32,
+) -> VerbatimRun {
+    let matches =
+        |a: &[f32; OUT], b: &[f32; OUT]| a.iter().zip(b).all(|(a, b)| (a - b).abs() <= tolerance);
+
+    let mut best = VerbatimRun::default();
+    // run lengths ending at the previous generated frame, one per training frame
+    let mut previous = vec![0; training.len() + 1];
+    let mut current = vec![0; training.len() + 1];
+
+    for (g, frame) in generated.iter().enumerate() {
+        for (t, train) in training.iter().enumerate() {
+            current[t + 1] = if matches(frame, train) {
+                previous[t] + 1
+            } else {
+                0
+            };
+
+            if current[t + 1] > best.length {
+                best = VerbatimRun {
+                    length: current[t + 1],
+                    generated_start: g + 1 - current[t + 1],
+                    source: Source {
+                        clip: 0,
+                        frame: t + 1 - current[t + 1],
+                    },
+                };
+            }
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    best
+}
+
+impl<const OUT: usize> Dataset<OUT> {
+    // longest_verbatim_run against every clip, runs don't carry on from one clip into the next
+    pub fn longest_verbatim_run(&self, generated: &[[f32; OUT]], tolerance: f32) -> VerbatimRun {
+        self.clips
+            .iter()
+            .enumerate()
+            .map(|(c, clip)| {
+                let mut run = longest_verbatim_run(generated, &clip.frames, tolerance);
+                run.source.clip = c;
+                run
+            })
+            .max_by_key(|run| run.length)
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frames(xs: &[f32]) -> Vec<[f32; 1]> {
+        xs.iter().map(|x| [*x]).collect()
+    }
+
+    #[test]
+    fn finds_the_longest_run_and_where_it_is() {
+        let generated = frames(&[7.0, 1.0, 2.0, 9.0, 2.0, 3.0, 4.0, 8.0]);
+        let training = frames(&[0.0, 1.0, 2.0, 3.0, 4.0, 5.0]);
+
+        let run = longest_verbatim_run(&generated, &training, 0.0);
+        assert_eq!(
+            run,
+            VerbatimRun {
+                length: 3,
+                generated_start: 4,
+                source: Source { clip: 0, frame: 2 },
+            }
+        );
+    }
+
+    #[test]
+    fn frames_within_tolerance_match() {
+        let generated = frames(&[1.05, 1.95]);
+        let training = frames(&[1.0, 2.0]);
+
+        assert_eq!(longest_verbatim_run(&generated, &training, 0.0).length, 0);
+        assert_eq!(longest_verbatim_run(&generated, &training, 0.1).length, 2);
+    }
+
+    #[test]
+    fn runs_stop_at_clip_boundaries() {
+        let mut dataset = Dataset::new();
+        dataset.push("a", frames(&[0.0, 1.0]));
+        dataset.push("b", frames(&[2.0, 3.0]));
+
+        // 1, 2, 3 only shows up if clip a ran straight into clip b
+        let run = dataset.longest_verbatim_run(&frames(&[1.0, 2.0, 3.0]), 0.0);
+        assert_eq!(
+            run,
+            VerbatimRun {
+                length: 2,
+                generated_start: 1,
+                source: Source { clip: 1, frame: 0 },
+            }
+        );
+    }
+}
@@ -0,0 +1,358 @@
+use std::collections::VecDeque;
+
+use crate::{
+    bmd::{Interpolate, PositionState, SpikeDist, BMD},
+    flat::{FlatIndex, FlatState},
+    lookback::FromHistory,
+};
+
+// the index, plus how to feed it states without needing Pos: FlatState everywhere
+#[derive(Debug)]
+struct Indexed<Pos> {
+    index: FlatIndex,
+    write_coords: fn(&Pos, &mut [f32]),
+    kernel: fn(f32) -> f32,
+}
+
+// How an OnlineBMD lets go of old transitions
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Memory {
+    // once there are more transitions than this the oldest get evicted, first in first out
+    pub capacity: Option<usize>,
+    // every frame of age multiplies a transition's weight by this, in (0, 1], 1.0 never forgets
+    pub forgetting: f32,
+}
+
+impl Memory {
+    pub fn unbounded() -> Self {
+        Memory {
+            capacity: None,
+            forgetting: 1.0,
+        }
+    }
+
+    pub fn window(capacity: usize) -> Self {
+        Memory {
+            capacity: Some(capacity),
+            forgetting: 1.0,
+        }
+    }
+
+    pub fn forgetting(factor: f32) -> Self {
+        let memory = Memory {
+            capacity: None,
+            forgetting: factor,
+        };
+        memory.validate();
+        memory
+    }
+
+    fn validate(&self) {
+        assert!(
+            self.forgetting > 0.0 && self.forgetting <= 1.0,
+            "forgetting factor has to be in (0, 1], got {}",
+            self.forgetting
+        );
+    }
+}
+
+// A BMD that keeps learning from frames as they stream in.
+// It holds on to the last SPAN frames itself, so every observed frame after the first SPAN becomes a
+// new transition. Removing old transitions keeps the optional FlatIndex in step with the model.
+// Age weights from `memory` multiply the similarities, so every BlendedDist sees recent data favoured.
+#[derive(Debug)]
+pub struct OnlineBMD<Pos: PositionState, const OUT: usize> {
+    bmd: BMD<Pos, SpikeDist<[f32; OUT]>>,
+    window: VecDeque<[f32; OUT]>,
+    indexed: Option<Indexed<Pos>>,
+    // the frame count each transition was added at, lined up with the BMD
+    added: VecDeque<u64>,
+    frames_seen: u64,
+    pub side_len: f32,
+    // private so a new Memory always goes through validate
+    memory: Memory,
+}
+
+impl<Pos, const OUT: usize> OnlineBMD<Pos, OUT>
+where
+    Pos: PositionState + FromHistory<OUT>,
+{
+    pub fn new(side_len: f32) -> Self {
+        OnlineBMD {
+            bmd: BMD {
+                distributions: Vec::new(),
+            },
+            window: VecDeque::with_capacity(Pos::SPAN + 1),
+            indexed: None,
+            added: VecDeque::new(),
+            frames_seen: 0,
+            side_len,
+            memory: Memory::unbounded(),
+        }
+    }
+
+    pub fn with_memory(mut self, memory: Memory) -> Self {
+        self.set_memory(memory);
+        self
+    }
+
+    pub fn memory(&self) -> Memory {
+        self.memory
+    }
+
+    // swaps the memory, evicting right away if the new window is smaller, returns how many went
+    pub fn set_memory(&mut self, memory: Memory) -> usize {
+        memory.validate();
+        self.memory = memory;
+        match memory.capacity {
+            Some(capacity) => self.expire_to(capacity),
+            None => 0,
+        }
+    }
+
+    pub fn bmd(&self) -> &BMD<Pos, SpikeDist<[f32; OUT]>> {
+        &self.bmd
+    }
+
+    pub fn index(&self) -> Option<&FlatIndex> {
+        self.indexed.as_ref().map(|i| &i.index)
+    }
+
+    pub fn len(&self) -> usize {
+        self.bmd.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bmd.is_empty()
+    }
+
+    // Takes in the next frame, adding a transition once there is enough history before it.
+    // Returns how many transitions the memory window evicted, for SuccessorSearch::removed_oldest
+    pub fn observe(&mut self, frame: [f32; OUT]) -> usize {
+        if self.window.len() == Pos::SPAN {
+            let pos = Pos::from_history(self.window.make_contiguous());
+
+            if let Some(indexed) = &mut self.indexed {
+                let mut coords = vec![0.0; indexed.index.dim()];
+                (indexed.write_coords)(&pos, &mut coords);
+                indexed.index.push_coords(&coords);
+            }
+
+            self.bmd.push(
+                pos,
+                SpikeDist {
+                    pos: frame,
+                    side_len: self.side_len,
+                },
+            );
+
+            self.added.push_back(self.frames_seen);
+
+            self.window.pop_front();
+        }
+
+        self.window.push_back(frame);
+        self.frames_seen += 1;
+
+        match self.memory.capacity {
+            Some(capacity) => self.expire_to(capacity),
+            None => 0,
+        }
+    }
+
+    // forget the running history, so the next frames don't get stitched onto the old ones
+    pub fn break_sequence(&mut self) {
+        self.window.clear();
+    }
+
+    // drops the `count` oldest transitions, returning how many there actually were
+    pub fn remove_oldest(&mut self, count: usize) -> usize {
+        let count = count.min(self.len());
+        self.bmd.remove_oldest(count);
+        self.added.drain(..count);
+        if let Some(indexed) = &mut self.indexed {
+            indexed.index.remove_oldest(count);
+        }
+        count
+    }
+
+    // drops the oldest transitions until there are at most `max_len` left, returning how many went
+    pub fn expire_to(&mut self, max_len: usize) -> usize {
+        self.remove_oldest(self.len().saturating_sub(max_len))
+    }
+
+    // the history the next observed frame would be predicted from, if there is enough of it yet
+    pub fn current_state(&mut self) -> Option<Pos> {
+        (self.window.len() == Pos::SPAN).then(|| Pos::from_history(self.window.make_contiguous()))
+    }
+}
+
+impl<Pos, const OUT: usize> OnlineBMD<Pos, OUT>
+where
+    Pos: FlatState + FromHistory<OUT>,
+{
+    // start keeping a FlatIndex of every stored state, interpolate uses it from then on
+    pub fn with_index(mut self) -> Self {
+        self.indexed = Some(Indexed {
+            index: FlatIndex::new(&self.bmd),
+            write_coords: Pos::write_coords,
+            kernel: Pos::kernel,
+        });
+        self
+    }
+}
+
+impl<Pos: PositionState, const OUT: usize> OnlineBMD<Pos, OUT> {
+    fn age_weight(&self, added: u64) -> f32 {
+        let age = self.frames_seen - added - 1;
+        self.memory.forgetting.powi(age.min(i32::MAX as u64) as i32)
+    }
+
+    // how much each stored transition still counts for, by how many frames ago it was added
+    pub fn age_weights(&self) -> impl Iterator<Item = f32> + '_ {
+        self.added.iter().map(|&added| self.age_weight(added))
+    }
+}
+
+impl<Pos: PositionState, const OUT: usize> Interpolate for OnlineBMD<Pos, OUT> {
+    type Pos = Pos;
+    type Dist = SpikeDist<[f32; OUT]>;
+
+    fn pairs(&self) -> &[(Pos, SpikeDist<[f32; OUT]>)] {
+        &self.bmd.distributions
+    }
+
+    fn pair_weight(&self, pair: usize) -> f32 {
+        self.age_weight(self.added[pair])
+    }
+
+    // similarities come from the index when there is one
+    fn weights<'s>(&'s self, eval_pos: &'s Pos) -> impl Iterator<Item = f32> + 's {
+        let similarities: Vec<f32> = match &self.indexed {
+            Some(indexed) => {
+                let mut query = vec![0.0; indexed.index.dim()];
+                (indexed.write_coords)(eval_pos, &mut query);
+
+                indexed.index.similarities_of(&query, indexed.kernel)
+            }
+            None => self
+                .bmd
+                .distributions
+                .iter()
+                .map(|(pos, _)| pos.similarity(eval_pos))
+                .collect(),
+        };
+
+        similarities
+            .into_iter()
+            .zip(self.age_weights())
+            .map(|(similarity, age)| similarity * age)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bmd::WeightedSpikes, lookback::Lookback, successor::SuccessorSearch};
+
+    fn frame(t: usize) -> [f32; 2] {
+        let t = t as f32;
+        [0.02 * (0.3 * t).sin(), 0.02 * (0.11 * t).cos()]
+    }
+
+    #[test]
+    fn streamed_index_matches_a_fresh_one() {
+        let mut online = OnlineBMD::<Lookback<3, 2>, 2>::new(0.0).with_index();
+
+        for t in 0..200 {
+            online.observe(frame(t));
+            if t % 25 == 0 {
+                online.expire_to(40);
+            }
+            if t == 120 {
+                online.break_sequence();
+            }
+        }
+
+        let query = online.current_state().unwrap();
+        let fresh = FlatIndex::new(online.bmd());
+        let streamed = online.index().unwrap();
+
+        assert_eq!(streamed.len(), online.len());
+        assert_eq!(streamed.similarities(&query), fresh.similarities(&query));
+    }
+
+    #[test]
+    fn forgetting_weights_fall_off_with_age() {
+        let mut online =
+            OnlineBMD::<Lookback<3, 2>, 2>::new(0.0).with_memory(Memory::forgetting(0.5));
+        for t in 0..7 {
+            online.observe(frame(t));
+        }
+
+        let weights: Vec<f32> = online.age_weights().collect();
+        assert_eq!(weights, [0.125, 0.25, 0.5, 1.0]);
+    }
+
+    #[test]
+    #[should_panic(expected = "forgetting factor")]
+    fn forgetting_everything_is_rejected() {
+        Memory::forgetting(0.0);
+    }
+
+    #[test]
+    fn window_evicts_oldest_first() {
+        let mut online = OnlineBMD::<Lookback<3, 2>, 2>::new(0.0).with_memory(Memory::window(5));
+        for t in 0..20 {
+            online.observe(frame(t));
+        }
+
+        assert_eq!(online.len(), 5);
+        assert_eq!(online.bmd().distributions[0].1.pos, frame(15));
+    }
+
+    #[test]
+    fn evictions_are_counted_for_successor_search() {
+        let mut online = OnlineBMD::<Lookback<3, 2>, 2>::new(0.0).with_memory(Memory::window(5));
+        let mut search = SuccessorSearch::new(2, 0, 0.0);
+
+        let evicted: Vec<usize> = (0..12)
+            .map(|t| {
+                let evicted = online.observe(frame(t));
+                search.removed_oldest(evicted);
+                if let Some(pos) = online.current_state() {
+                    let _: WeightedSpikes<[f32; 2]> = search.interpolate(&online, pos);
+                }
+                evicted
+            })
+            .collect();
+
+        // transitions start at frame 3, the window fills at frame 7 and evicts one a frame after that
+        assert_eq!(evicted, [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1]);
+        assert_eq!(online.expire_to(2), 3);
+        assert_eq!(online.remove_oldest(10), 2);
+        assert!(online.is_empty());
+    }
+
+    #[test]
+    fn set_memory_validates_and_applies_the_window() {
+        let mut online = OnlineBMD::<Lookback<3, 2>, 2>::new(0.0);
+        for t in 0..10 {
+            online.observe(frame(t));
+        }
+
+        assert_eq!(online.set_memory(Memory::window(4)), 3);
+        assert_eq!(online.memory(), Memory::window(4));
+        assert_eq!(online.len(), 4);
+    }
+
+    #[test]
+    #[should_panic(expected = "forgetting factor")]
+    fn negative_forgetting_is_rejected_by_set_memory() {
+        let mut online = OnlineBMD::<Lookback<3, 2>, 2>::new(0.0);
+        online.set_memory(Memory {
+            capacity: None,
+            forgetting: -0.5,
+        });
+    }
+}
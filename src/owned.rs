@@ -0,0 +1,256 @@
+use std::sync::Arc;
+
+use rand::Rng;
+
+use crate::{
+    bmd::{BlendedDist, PositionState, SpikeDist, BMD},
+    distribution::{AvgCUD, Sample, CUD, PDF},
+};
+
+// A single stored distribution, the kind of thing blended distributions are made of
+pub trait Component {
+    type OutputState;
+
+    fn evaluate(&self, x: &Self::OutputState) -> f32;
+
+    fn sample_with<R: Rng + ?Sized>(&self, rng: &mut R) -> Self::OutputState;
+
+    // how much more likely this one is to be sampled from than its blend weight alone says
+    fn mass(&self) -> f32 {
+        1.0
+    }
+}
+
+impl<const N: usize> Component for SpikeDist<[f32; N]> {
+    type OutputState = [f32; N];
+
+    fn evaluate(&self, x: &[f32; N]) -> f32 {
+        SpikeDist::evaluate(self, x)
+    }
+
+    fn sample_with<R: Rng + ?Sized>(&self, rng: &mut R) -> [f32; N] {
+        SpikeDist::sample_with(self, rng)
+    }
+}
+
+impl Component for CUD {
+    type OutputState = f32;
+
+    fn evaluate(&self, x: &f32) -> f32 {
+        PDF::evaluate(self, *x)
+    }
+
+    fn sample_with<R: Rng + ?Sized>(&self, rng: &mut R) -> f32 {
+        Sample::sample_with(self, rng)
+    }
+}
+
+impl Component for AvgCUD {
+    type OutputState = f32;
+
+    fn evaluate(&self, x: &f32) -> f32 {
+        PDF::evaluate(self, *x)
+    }
+
+    fn sample_with<R: Rng + ?Sized>(&self, rng: &mut R) -> f32 {
+        Sample::sample_with(self, rng)
+    }
+
+    // WeightedAvgCUD picks any of its CUDs by weight times length, and sample_with picks
+    // a CUD by length, so picking the AvgCUD by its total length lands on the same odds
+    fn mass(&self) -> f32 {
+        self.cuds.iter().map(CUD::len).sum()
+    }
+}
+
+impl<C: Component> Component for Arc<C> {
+    type OutputState = C::OutputState;
+
+    fn evaluate(&self, x: &Self::OutputState) -> f32 {
+        C::evaluate(self, x)
+    }
+
+    fn sample_with<R: Rng + ?Sized>(&self, rng: &mut R) -> Self::OutputState {
+        C::sample_with(self, rng)
+    }
+
+    fn mass(&self) -> f32 {
+        C::mass(self)
+    }
+}
+
+// A blended distribution that owns its components instead of borrowing them from the BMD,
+// so it can be stored, sent to another thread or kept while the model changes.
+// Components are cloned in, so store them as Arc<Dist> (see BMD::shared) to keep that cheap.
+// Pairs with no weight are left out, only their weight is kept for neighbour_weights.
+#[derive(Debug, Clone)]
+pub struct OwnedBlend<Dist> {
+    weights: Vec<f32>,
+    dists: Vec<Dist>,
+    // every weight given, zeros included
+    neighbours: Vec<f32>,
+}
+
+impl<Dist> Default for OwnedBlend<Dist> {
+    fn default() -> Self {
+        OwnedBlend {
+            weights: Vec::new(),
+            dists: Vec::new(),
+            neighbours: Vec::new(),
+        }
+    }
+}
+
+impl<Dist> OwnedBlend<Dist> {
+    pub fn weights(&self) -> &[f32] {
+        &self.weights
+    }
+
+    pub fn dists(&self) -> &[Dist] {
+        &self.dists
+    }
+}
+
+impl<'a, Dist: Component + Clone + 'a> BlendedDist<'a, &'a Dist> for OwnedBlend<Dist> {
+    type OutputState = Dist::OutputState;
+
+    fn from<T>(weighted_dists: T) -> Self
+    where
+        T: IntoIterator<Item = (f32, &'a Dist)>,
+    {
+        let mut owned = OwnedBlend::default();
+        owned.refill(weighted_dists);
+        owned
+    }
+
+    fn refill<T>(&mut self, weighted_dists: T)
+    where
+        T: IntoIterator<Item = (f32, &'a Dist)>,
+    {
+        self.weights.clear();
+        self.dists.clear();
+        self.neighbours.clear();
+        for (w, d) in weighted_dists {
+            self.neighbours.push(w);
+            if w > 0.0 {
+                self.weights.push(w);
+                self.dists.push(d.clone());
+            }
+        }
+    }
+
+    fn evalutate(&self, eval_pos: Self::OutputState) -> f32 {
+        self.weights
+            .iter()
+            .zip(&self.dists)
+            .map(|(w, dist)| dist.evaluate(&eval_pos) * w)
+            .sum::<f32>()
+            / self.weights.iter().sum::<f32>()
+    }
+
+    // unlike weights(), the ones with no weight are still in here
+    fn neighbour_weights(&self) -> &[f32] {
+        &self.neighbours
+    }
+
+    fn sample_with<R: Rng + ?Sized>(&self, rng: &mut R) -> Self::OutputState {
+        let index = match pick_by_mass(&self.weights, &self.dists, rng) {
+            Some(i) => i,
+            None => panic!("We couldn't find anything similar!"),
+        };
+
+        self.dists[index].sample_with(rng)
+    }
+}
+
+// pick_weighted, with every weight scaled by its component's mass
+fn pick_by_mass<C: Component, R: Rng + ?Sized>(
+    weights: &[f32],
+    dists: &[C],
+    rng: &mut R,
+) -> Option<usize> {
+    let masses = || weights.iter().zip(dists).map(|(w, d)| w * d.mass());
+    let total: f32 = masses().sum();
+    if !(total > 0.0 && total.is_finite()) {
+        return None;
+    }
+
+    let cutoff = rng.gen::<f32>() * total;
+    let mut bar = 0.0;
+    let mut last = None;
+    for (i, m) in masses().enumerate() {
+        bar += m;
+        if bar > cutoff {
+            return Some(i);
+        }
+        if m > 0.0 {
+            last = Some(i);
+        }
+    }
+
+    // rounding can leave us just short of the total, same as pick_weighted
+    last
+}
+
+impl<Pos: PositionState, Dist> BMD<Pos, Dist> {
+    // puts every distribution behind an Arc, so OwnedBlends of it only bump reference counts
+    pub fn shared(self) -> BMD<Pos, Arc<Dist>> {
+        BMD {
+            distributions: self
+                .distributions
+                .into_iter()
+                .map(|(pos, dist)| (pos, Arc::new(dist)))
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+    use crate::distribution::WeightedAvgCUD;
+
+    // how often samples land below 1.0, where only the narrow AvgCUD is
+    fn share_below_one<'a, T: BlendedDist<'a, &'a AvgCUD, OutputState = f32>>(blend: &T) -> f32 {
+        let mut rng = StdRng::seed_from_u64(3);
+        let below = (0..20_000)
+            .filter(|_| blend.sample_with(&mut rng) < 1.0)
+            .count();
+        below as f32 / 20_000.0
+    }
+
+    #[test]
+    fn owned_avgcud_samples_like_weighted_avgcud() {
+        let narrow = AvgCUD {
+            cuds: vec![CUD { a: 0.0, b: 0.5 }],
+        };
+        let wide = AvgCUD {
+            cuds: vec![CUD { a: 2.0, b: 4.0 }, CUD { a: 5.0, b: 9.0 }],
+        };
+        let weighted = [(0.5, &narrow), (0.5, &wide)];
+
+        let borrowed: WeightedAvgCUD = BlendedDist::from(weighted);
+        let owned: OwnedBlend<AvgCUD> = BlendedDist::from(weighted);
+
+        // 0.5 * 0.5 out of 0.5 * 0.5 + 0.5 * 6.0
+        let expected = 0.25 / 3.25;
+        assert!((share_below_one(&borrowed) - expected).abs() < 0.01);
+        assert!((share_below_one(&owned) - expected).abs() < 0.01);
+    }
+
+    #[test]
+    fn neighbour_weights_line_up_with_the_pairs() {
+        let spikes = [0.0, 1.0, 2.0].map(|x| SpikeDist {
+            pos: [x],
+            side_len: 0.0,
+        });
+        let weighted = [(0.5, &spikes[0]), (0.0, &spikes[1]), (0.25, &spikes[2])];
+
+        let owned: OwnedBlend<SpikeDist<[f32; 1]>> = BlendedDist::from(weighted);
+
+        assert_eq!(owned.neighbour_weights(), [0.5, 0.0, 0.25]);
+        assert_eq!(owned.weights(), [0.5, 0.25]);
+    }
+}
@@ -0,0 +1,123 @@
+use std::io::{self, Write};
+
+use crate::{
+    bmd::{effective_sample_size, Interpolate, PositionState, Reweight},
+    dataset::{ClipBMD, Dataset, Source},
+};
+
+// One training pair's share of a blend
+#[derive(Debug, Clone, Copy)]
+pub struct Contribution {
+    pub source: Source,
+    // out of the total weight of the blend, so all of them add up to 1
+    pub weight: f32,
+}
+
+// What a generated frame was blended from: the heaviest pairs, most first, and how many really counted
+#[derive(Debug, Clone)]
+pub struct Provenance {
+    pub top: Vec<Contribution>,
+    pub effective_sample_size: f32,
+}
+
+// Generated frames, each with where it came from
+#[derive(Debug, Clone)]
+pub struct Explained<const OUT: usize> {
+    pub frames: Vec<[f32; OUT]>,
+    pub provenance: Vec<Provenance>,
+}
+
+impl<Pos: PositionState, const OUT: usize> ClipBMD<Pos, OUT> {
+    // every pair's blending weight at eval_pos
+    fn blend_weights(&self, eval_pos: &Pos) -> Vec<f32> {
+        self.model.weights(eval_pos).collect()
+    }
+
+    fn provenance_of(&self, weights: &[f32], top: usize) -> Provenance {
+        let total: f32 = weights.iter().sum();
+
+        let mut ranked: Vec<usize> = (0..weights.len()).filter(|&i| weights[i] > 0.0).collect();
+        ranked.sort_by(|&a, &b| weights[b].total_cmp(&weights[a]));
+        ranked.truncate(top);
+
+        Provenance {
+            top: ranked
+                .into_iter()
+                .map(|i| Contribution {
+                    source: self.sources[i],
+                    weight: weights[i] / total,
+                })
+                .collect(),
+            effective_sample_size: effective_sample_size(weights),
+        }
+    }
+
+    // the `top` pairs the blend at eval_pos leans on most
+    pub fn explain(&self, eval_pos: &Pos, top: usize) -> Provenance {
+        self.provenance_of(&self.blend_weights(eval_pos), top)
+    }
+}
+
+// Keeps the `top` contributions behind every step of a rollout of its model, as a Reweight.
+// Put it after any other Reweight to see the weights that really get blended.
+#[derive(Debug)]
+pub struct Explainer<'m, Pos: PositionState, const OUT: usize> {
+    trained: &'m ClipBMD<Pos, OUT>,
+    top: usize,
+    pub provenance: Vec<Provenance>,
+}
+
+impl<Pos: PositionState, const OUT: usize> ClipBMD<Pos, OUT> {
+    // for generate_reweighted on self.model
+    pub fn explainer(&self, top: usize) -> Explainer<'_, Pos, OUT> {
+        Explainer {
+            trained: self,
+            top,
+            provenance: Vec::new(),
+        }
+    }
+}
+
+impl<Pos: PositionState, const OUT: usize> Reweight for Explainer<'_, Pos, OUT> {
+    fn reweight(&mut self, _step: usize, _length: usize, weights: &mut [f32]) {
+        self.provenance
+            .push(self.trained.provenance_of(weights, self.top));
+    }
+}
+
+impl<Pos: PositionState, const OUT: usize> Explainer<'_, Pos, OUT> {
+    // pairs the frames generated with it up with their provenance
+    pub fn explain(self, frames: Vec<[f32; OUT]>) -> Explained<OUT> {
+        assert_eq!(frames.len(), self.provenance.len(), "one step per frame");
+
+        Explained {
+            frames,
+            provenance: self.provenance,
+        }
+    }
+}
+
+impl<const OUT: usize> Explained<OUT> {
+    // Tab separated, one line per frame: its channels, the effective sample size,
+    // then clip:frame:weight for each contribution. Clip names come from the dataset trained on.
+    pub fn write_tsv<W: Write>(&self, dataset: &Dataset<OUT>, out: &mut W) -> io::Result<()> {
+        for (frame, provenance) in self.frames.iter().zip(&self.provenance) {
+            for channel in frame {
+                write!(out, "{channel}\t")?;
+            }
+            write!(out, "{}", provenance.effective_sample_size)?;
+
+            for contribution in &provenance.top {
+                let Source { clip, frame } = contribution.source;
+                write!(
+                    out,
+                    "\t{}:{}:{}",
+                    dataset.clips[clip].name, frame, contribution.weight
+                )?;
+            }
+            writeln!(out)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,136 @@
+use rand::{thread_rng, Rng};
+
+use crate::bmd::{BlendedDist, Interpolate};
+
+// Speeds up rollouts by guessing where the neighbours will be next frame.
+// If stored pair i had a high weight this frame, pair i + 1 probably will next frame, so only those
+// successors plus a few random pairs get scored, falling back to a full search when they don't
+// add up to min_weight. Assumes pair i + 1 follows pair i, which from_series gives you.
+// Candidates are scored with Interpolate::weight_at, so either search gives the same weights.
+// VariableOrderBMD has to score every pair for each of those, so it gains nothing from this.
+#[derive(Debug, Clone)]
+pub struct SuccessorSearch {
+    // how many of the best neighbours to follow into the next frame
+    pub keep: usize,
+    // how many random pairs to also try each frame
+    pub explore: usize,
+    // the total weight the candidates need, otherwise everything gets searched
+    pub min_weight: f32,
+    previous: Vec<usize>,
+    pub full_searches: usize,
+    pub fast_searches: usize,
+}
+
+impl SuccessorSearch {
+    pub fn new(keep: usize, explore: usize, min_weight: f32) -> Self {
+        SuccessorSearch {
+            keep,
+            explore,
+            min_weight,
+            previous: Vec::new(),
+            full_searches: 0,
+            fast_searches: 0,
+        }
+    }
+
+    // forget the previous neighbours, for when a new rollout starts
+    pub fn reset(&mut self) {
+        self.previous.clear();
+    }
+
+    // call after BMD::remove_oldest, or with what OnlineBMD::observe evicted, so the remembered
+    // neighbours still point at the same pairs
+    pub fn removed_oldest(&mut self, count: usize) {
+        self.previous.retain(|&i| i >= count);
+        self.previous.iter_mut().for_each(|i| *i -= count);
+    }
+
+    fn remember_best(&mut self, weighted: &[(usize, f32)]) {
+        let mut best = weighted.to_vec();
+        best.sort_by(|a, b| b.1.total_cmp(&a.1));
+        self.previous = best.into_iter().take(self.keep).map(|(i, _)| i).collect();
+    }
+
+    pub fn interpolate<'a, M, T>(&mut self, model: &'a M, eval_pos: M::Pos) -> T
+    where
+        M: Interpolate,
+        M::Dist: 'a,
+        T: BlendedDist<'a, &'a M::Dist> + 'a,
+    {
+        let pairs = model.pairs();
+        let len = pairs.len();
+        let mut rng = thread_rng();
+
+        let mut candidates: Vec<usize> = self
+            .previous
+            .iter()
+            .map(|i| i + 1)
+            .filter(|&i| i < len)
+            .chain((0..self.explore.min(len)).map(|_| rng.gen_range(0..len)))
+            .collect();
+        candidates.sort_unstable();
+        candidates.dedup();
+
+        let weighted: Vec<(usize, f32)> = candidates
+            .into_iter()
+            .map(|i| (i, model.weight_at(i, &eval_pos)))
+            .collect();
+
+        let weighted = if !self.previous.is_empty()
+            && weighted.iter().map(|(_, w)| w).sum::<f32>() >= self.min_weight
+        {
+            self.fast_searches += 1;
+            weighted
+        } else {
+            self.full_searches += 1;
+            model.weights(&eval_pos).enumerate().collect()
+        };
+
+        self.remember_best(&weighted);
+
+        // pairs that weren't scored still go in with no weight, so neighbour_weights lines up with the pairs
+        let mut weights = vec![0.0; len];
+        for (i, w) in weighted {
+            weights[i] = w;
+        }
+
+        T::from(weights.into_iter().zip(pairs.iter().map(|(_, dist)| dist)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        bmd::{SpikeDist, WeightedSpikes, BMD},
+        lookback::{FromHistory, Lookback},
+        variable_order::VariableOrderBMD,
+    };
+
+    #[test]
+    fn fast_search_weights_match_the_full_ones() {
+        let series: Vec<[f32; 1]> = (0..12).map(|i| [i as f32 * 0.05]).collect();
+        let model = VariableOrderBMD::new(
+            BMD::<Lookback<2, 1>, SpikeDist<[f32; 1]>>::from_series(&series, 0.0),
+            vec![1, 2],
+            1.0,
+        );
+        let mut search = SuccessorSearch::new(3, 0, 0.0);
+
+        for t in 2..6 {
+            let pos = Lookback::from_history(&series[..t]);
+            let blend: WeightedSpikes<[f32; 1]> = search.interpolate(&model, pos);
+            let full: Vec<f32> = model.weights(&pos).collect();
+
+            let weights = blend.neighbour_weights();
+            assert_eq!(weights.len(), full.len());
+            for (w, f) in weights.iter().zip(&full) {
+                // pairs left out of a fast search have no weight, the rest match
+                assert!(*w == 0.0 || (w - f).abs() < 1e-6);
+            }
+        }
+
+        assert_eq!(search.full_searches, 1);
+        assert_eq!(search.fast_searches, 3);
+    }
+}
@@ -1,2 +1,17 @@
 pub mod s12_locrot;
 pub mod s12_loc;
+pub mod s12_varorder;
+pub mod s_posevel;
+pub mod s_phase;
+pub mod s_control;
+pub mod s_keyframe;
+pub mod s_infill;
+pub mod s_leadin;
+pub mod s_beam;
+pub mod s_successor;
+pub mod s_condense;
+pub mod s_dedup;
+pub mod s_weighted;
+pub mod s_clips;
+pub mod s_diagnose;
+pub mod s_novelty;
@@ -1,15 +1,18 @@
 use std::collections::VecDeque;
 
-use crate::{bmd::{PositionState, BMD, SpikeDist, WeightedSpikes, BlendedDist}, data};
+use crate::{bmd::{PositionState, BMD, SpikeDist, WeightedSpikes, BlendedDist, Interpolate}, data};
 
 const OUT: usize = 3;
 const LOOKBACK: usize = 1;
 
-impl PositionState for [f32;OUT*LOOKBACK] {
+#[derive(Debug)]
+struct Loc(pub [f32;OUT*LOOKBACK]);
+
+impl PositionState for Loc {
     fn similarity(&self, other: &Self) -> f32 {
-        let distance = self
+        let distance = self.0
             .iter()
-            .zip(other.iter())
+            .zip(other.0.iter())
             .map(|(u, v)| (u - v) * (u - v))
             .sum::<f32>()
             .sqrt();
@@ -23,11 +26,11 @@ impl PositionState for [f32;OUT*LOOKBACK] {
 pub fn go() {
     let delta = 0.1;
 
-    let mut sword_bmd: BMD<[f32;OUT*LOOKBACK], SpikeDist<[f32;OUT]>> = BMD {
+    let mut sword_bmd: BMD<Loc, SpikeDist<[f32;OUT]>> = BMD {
         distributions: vec![],
     };
 
-    let dif_data: Vec<[f32;OUT]> = data::sword_6().windows(2).map(|win| {
+    let _dif_data: Vec<[f32;OUT]> = data::sword_6().windows(2).map(|win| {
         let mut buf = [0.0; OUT];
 
         for i in 0..buf.len() {
@@ -45,7 +48,7 @@ pub fn go() {
             chunk.clone_from_slice(&window[i]);
         }
         sword_bmd.distributions.push((
-            buf,
+            Loc(buf),
             SpikeDist {
                 pos: window[LOOKBACK],
                 side_len: delta
@@ -57,9 +60,9 @@ pub fn go() {
 
     let mut current = [0.0; OUT];
 
-    let mut last = VecDeque::from_iter(data.iter().take(LOOKBACK).map(|l| l.clone()));
+    let mut last = VecDeque::from_iter(data.iter().take(LOOKBACK).copied());
 
-    for i in 0..150 {
+    for _ in 0..150 {
         let last_buf = last.back().unwrap();
         for i in 0..current.len() {
             current[i] += last_buf[i];
@@ -70,7 +73,7 @@ pub fn go() {
         for (j, chunk) in buf.chunks_mut(OUT).enumerate() {
             chunk.clone_from_slice(&last[j]);
         }
-        let new = sword_bmd.interpolate::<WeightedSpikes<[f32;OUT]>>(buf).sample();
+        let new = sword_bmd.interpolate::<WeightedSpikes<[f32;OUT]>>(Loc(buf)).sample();
         last.pop_front();
         last.push_back(new);
     }
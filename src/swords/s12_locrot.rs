@@ -1,28 +1,11 @@
-use std::{collections::VecDeque, io::BufWriter, fs::File, path::Path};
+use std::{collections::VecDeque, fs::File};
 
-use crate::{bmd::{PositionState, BMD, SpikeDist, WeightedSpikes, BlendedDist}, data};
+use crate::{bmd::{PositionState, BMD, SpikeDist, WeightedSpikes, BlendedDist, Interpolate}, data};
 
 struct LB12Dot(pub [f32;72]);
+#[allow(dead_code)]
 struct LB12Temporal(pub [f32;72]);
 
-impl PositionState for [f32;72] {
-    fn similarity(&self, other: &Self) -> f32 {
-        let distance = self
-            .iter()
-            .zip(other.iter())
-            .enumerate()
-            .map(|(i, (u, v))| (u - v) * (u - v) * (i as f32 + 1.0))
-            .sum::<f32>()
-            .sqrt();
-
-        const WIDENESS: f32 = 0.1;
-
-        // dbg!(distance);
-
-        f32::exp(-distance / WIDENESS)
-    }
-}
-
 impl PositionState for LB12Dot {
     fn similarity(&self, other: &Self) -> f32 {
         let distance = self.0
@@ -62,7 +45,7 @@ impl PositionState for LB12Dot {
 pub fn go() -> std::io::Result<()> {
     let delta = 0.0;
 
-    let mut sword_bmd: BMD<[f32;6*12], SpikeDist<[f32;6]>> = BMD {
+    let mut sword_bmd: BMD<LB12Dot, SpikeDist<[f32;6]>> = BMD {
         distributions: vec![],
     };
 
@@ -83,7 +66,7 @@ pub fn go() -> std::io::Result<()> {
             chunk.clone_from_slice(&window[i]);
         }
         sword_bmd.distributions.push((
-            buf,
+            LB12Dot(buf),
             SpikeDist {
                 pos: window[12],
                 side_len: delta
@@ -93,16 +76,14 @@ pub fn go() -> std::io::Result<()> {
 
     let mut current = data::sword_6()[12];
 
-    let mut last = VecDeque::from_iter(std::iter::repeat(
-[-1.5459953546524048, -0.3006895184516907, 4.337007522583008, 0.3151423931121826, 0.016330672428011894, -1.4718228578567505]
-    ).take(12));
+    let _last = VecDeque::from_iter(std::iter::repeat_n([-1.5459953546524048, -0.3006895184516907, 4.337007522583008, 0.3151423931121826, 0.016330672428011894, -1.4718228578567505], 12));
 
-    let mut last = VecDeque::from_iter(dif_data.iter().take(12).map(|l| l.clone()));
+    let mut last = VecDeque::from_iter(dif_data.iter().take(12).copied());
 
 
-    let f = File::create("./12s_locrot_dot.pos")?;
+    let _f = File::create("./12s_locrot_dot.pos")?;
 
-    for i in 0..150 {
+    for _ in 0..150 {
         let last_buf = last.back().unwrap();
         for i in 0..6 {
             current[i] += last_buf[i];
@@ -113,10 +94,10 @@ pub fn go() -> std::io::Result<()> {
         for (j, chunk) in buf.chunks_mut(6).enumerate() {
             chunk.clone_from_slice(&last[j]);
         }
-        let new = sword_bmd.interpolate::<WeightedSpikes<[f32;6]>>(buf).sample();
+        let new = sword_bmd.interpolate::<WeightedSpikes<[f32;6]>>(LB12Dot(buf)).sample();
         last.pop_front();
         last.push_back(new);
     }
 
-    return Ok(())
+    Ok(())
 }
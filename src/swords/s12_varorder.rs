@@ -0,0 +1,48 @@
+use std::collections::VecDeque;
+
+use blended_markov_distribution::{
+    bmd::{BlendedDist, Interpolate, WeightedSpikes, BMD},
+    data,
+    lookback::{FromHistory, Lookback},
+    variable_order::{OrderSelection, VariableOrderBMD},
+};
+
+// same setup as s12_locrot, but backing off from 12 frames down to 1 when the long context has no matches
+pub fn go() {
+    let dif_data: Vec<[f32; 6]> = data::sword_6()
+        .windows(2)
+        .map(|win| {
+            let mut buf = [0.0; 6];
+            for i in 0..6 {
+                buf[i] = win[1][i] - win[0][i];
+            }
+            buf
+        })
+        .collect();
+
+    let mut sword_bmd = VariableOrderBMD::new(
+        BMD::<Lookback<12, 6>, _>::from_series(&dif_data, 0.0),
+        vec![1, 2, 4, 8, 12],
+        3.0,
+    );
+    sword_bmd.selection = OrderSelection::Mix;
+
+    let mut current = data::sword_6()[12];
+
+    let mut last = VecDeque::from_iter(dif_data.iter().take(12).copied());
+
+    for _ in 0..150 {
+        let last_buf = last.back().unwrap();
+        for i in 0..6 {
+            current[i] += last_buf[i];
+        }
+        println!("{:?}", current);
+
+        let pos = Lookback::<12, 6>::from_history(last.make_contiguous());
+        let new = sword_bmd
+            .interpolate::<WeightedSpikes<[f32; 6]>>(pos)
+            .sample();
+        last.pop_front();
+        last.push_back(new);
+    }
+}
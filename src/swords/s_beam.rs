@@ -0,0 +1,24 @@
+use blended_markov_distribution::{
+    beam::BeamSearch,
+    bmd::{WeightedSpikes, BMD},
+    data,
+    lookback::Lookback,
+};
+
+// the most likely 150 frames from the start of the swing instead of a random rollout
+pub fn go() {
+    let data = data::sword_6();
+
+    let sword_bmd: BMD<Lookback<4, 6>, _> = BMD::from_series(&data, 0.01);
+
+    let beam = BeamSearch::new(8, 4).best::<_, WeightedSpikes<[f32; 6]>, 6>(
+        &sword_bmd,
+        &data[..4],
+        150,
+    );
+
+    eprintln!("log density: {}", beam.log_density);
+    for frame in beam.frames {
+        println!("{:?}", frame);
+    }
+}
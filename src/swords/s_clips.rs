@@ -0,0 +1,67 @@
+use blended_markov_distribution::{
+    bmd::{Interpolate, WeightedSpikes},
+    data,
+    dataset::Dataset,
+    lookback::Lookback,
+};
+
+// the sword swing cut into three clips, training on two and holding the last one out
+pub fn go() {
+    let dataset = thirds();
+
+    let (training, validation) = dataset.split(|clip| clip.name == "swing_2");
+    let trained = training.train::<Lookback<12, 6>>(0.01);
+
+    for (c, clip) in training.clips.iter().enumerate() {
+        let pairs = trained.sources.iter().filter(|s| s.clip == c).count();
+        eprintln!(
+            "{}: {} frames, {} pairs",
+            clip.name,
+            clip.frames.len(),
+            pairs
+        );
+    }
+
+    eprintln!("held out: {} frames", validation.clips[0].frames.len());
+
+    // only as long as the clip it starts in, there is nothing to carry on from past its end
+    let first = &training.clips[0].frames;
+    let start = &first[..12];
+    for frame in trained
+        .model
+        .generate::<WeightedSpikes<[f32; 6]>, 6>(start, first.len() - 12)
+    {
+        println!("{:?}", frame);
+    }
+}
+
+fn thirds() -> Dataset<6> {
+    let frames = data::sword_6();
+    let third = frames.len() / 3;
+
+    let mut dataset = Dataset::new();
+    for (i, clip) in frames.chunks(third).enumerate() {
+        dataset.push(format!("swing_{i}"), clip.to_vec());
+    }
+    dataset
+}
+
+// a rollout through the first clip, each frame followed by the 3 training frames it leans on most
+pub fn explain() -> std::io::Result<()> {
+    let dataset = thirds();
+    let trained = dataset.train::<Lookback<12, 6>>(0.01);
+
+    let first = &dataset.clips[0].frames;
+    let mut explainer = trained.explainer(3);
+    let frames = trained
+        .model
+        .generate_reweighted::<WeightedSpikes<[f32; 6]>, _, 6>(
+            &first[..12],
+            first.len() - 12,
+            &mut explainer,
+        );
+
+    explainer
+        .explain(frames)
+        .write_tsv(&dataset, &mut std::io::stdout().lock())
+}
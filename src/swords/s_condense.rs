@@ -0,0 +1,33 @@
+use blended_markov_distribution::{
+    bmd::{BlendedDist, Interpolate, WeightedSpikes, BMD},
+    condense::Condense,
+    data,
+    lookback::{FromHistory, Lookback},
+};
+
+// condenses the 12 frame sword model at a few coverage radii, then rolls out the smallest one
+pub fn go() {
+    let frames = data::sword_6();
+
+    for min_similarity in [0.5, 0.1, 0.01] {
+        let bmd: BMD<Lookback<12, 6>, _> = BMD::from_series(&frames, 0.01);
+        let (_, report) = Condense::new(min_similarity).run(bmd);
+        eprintln!(
+            "min similarity {min_similarity}: {} -> {} pairs, {:?} nats lost per pair",
+            report.before, report.after, report.log_likelihood_lost
+        );
+    }
+
+    let bmd: BMD<Lookback<12, 6>, _> = BMD::from_series(&frames, 0.01);
+    let (condensed, _) = Condense::new(0.01).run(bmd);
+
+    let mut history = frames[..12].to_vec();
+    for _ in 0..150 {
+        let pos = Lookback::<12, 6>::from_history(&history[history.len() - 12..]);
+        let next = condensed
+            .interpolate::<WeightedSpikes<[f32; 6]>>(pos)
+            .sample();
+        println!("{:?}", next);
+        history.push(next);
+    }
+}
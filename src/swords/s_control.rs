@@ -0,0 +1,41 @@
+use blended_markov_distribution::{
+    bmd::{RExp, WeightedSpikes, BMD},
+    control::{Controlled, ControlledRollout},
+    data,
+    lookback::Lookback,
+};
+
+// steers the swing with a requested speed each frame, labelled from the recording itself
+pub fn go(speed: f32) {
+    let dif_data: Vec<[f32; 6]> = data::sword_6()
+        .windows(2)
+        .map(|win| {
+            let mut buf = [0.0; 6];
+            for i in 0..6 {
+                buf[i] = win[1][i] - win[0][i];
+            }
+            buf
+        })
+        .collect();
+
+    let speeds: Vec<RExp> = dif_data
+        .iter()
+        .map(|d| RExp((d[0] * d[0] + d[1] * d[1] + d[2] * d[2]).sqrt()))
+        .collect();
+
+    let sword_bmd: BMD<Controlled<Lookback<4, 6>, RExp>, _> =
+        BMD::from_controlled_series(&dif_data, &speeds, 0.0);
+
+    let new = sword_bmd.generate_controlled::<WeightedSpikes<[f32; 6]>, 6>(
+        &dif_data[..4],
+        std::iter::repeat_with(|| RExp(speed)).take(150),
+    );
+
+    let mut current = data::sword_6()[4];
+    for dif in new {
+        for i in 0..6 {
+            current[i] += dif[i];
+        }
+        println!("{:?}", current);
+    }
+}
@@ -0,0 +1,31 @@
+use blended_markov_distribution::{
+    bmd::{BlendedDist, Interpolate, WeightedSpikes, BMD},
+    data,
+    dedup::{Dedup, DuplicateAction},
+    lookback::{FromHistory, Lookback},
+};
+
+// the standing still frames sword_6 starts with, merged so they only count as much as they repeat
+pub fn go() {
+    let frames = data::sword_6();
+
+    let near = Dedup {
+        min_similarity: 0.9,
+        tolerance: 0.01,
+        action: DuplicateAction::Drop,
+    };
+    let (_, summary) = BMD::<Lookback<12, 6>, _>::from_series_dedup(&frames, 0.01, &near);
+    eprintln!("near duplicates dropped: {summary:?}");
+
+    let exact = Dedup::exact(DuplicateAction::Merge);
+    let (model, summary) = BMD::<Lookback<12, 6>, _>::from_series_dedup(&frames, 0.01, &exact);
+    eprintln!("exact duplicates merged: {summary:?}");
+
+    let mut history = frames[..12].to_vec();
+    for _ in 0..150 {
+        let pos = Lookback::<12, 6>::from_history(&history[history.len() - 12..]);
+        let next = model.interpolate::<WeightedSpikes<[f32; 6]>>(pos).sample();
+        println!("{:?}", next);
+        history.push(next);
+    }
+}
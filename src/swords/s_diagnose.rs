@@ -0,0 +1,28 @@
+use blended_markov_distribution::{
+    bmd::{Diagnose, Interpolate, WeightedSpikes, BMD},
+    data,
+    lookback::Lookback,
+};
+
+// the 12 frame sword rollout with point mass spikes, logging how many neighbours each step blends.
+// An effective sample size stuck near 1 means it is just replaying the training clip.
+pub fn go() {
+    let frames = data::sword_6();
+    let bmd: BMD<Lookback<12, 6>, _> = BMD::from_series(&frames, 0.0);
+
+    let mut diagnose = Diagnose::new(0.01);
+    let generated = bmd.generate_reweighted::<WeightedSpikes<[f32; 6]>, _, 6>(
+        &frames[..12],
+        120,
+        &mut diagnose,
+    );
+
+    eprintln!("step\tess\tentropy\tmax\tabove 1%");
+    for (step, (frame, d)) in generated.iter().zip(&diagnose.steps).enumerate() {
+        println!("{:?}", frame);
+        eprintln!(
+            "{step}\t{}\t{}\t{}\t{}",
+            d.effective_sample_size, d.entropy, d.max_weight, d.above_threshold
+        );
+    }
+}
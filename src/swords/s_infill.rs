@@ -0,0 +1,25 @@
+use blended_markov_distribution::{
+    bmd::{WeightedSpikes, BMD},
+    data,
+    infill::Infill,
+    lookback::{Direction, Lookback},
+};
+
+// drops 30 frames out of the middle of the swing and fills them back in
+pub fn go() {
+    let data = data::sword_6();
+
+    let forward: BMD<Lookback<4, 6>, _> = BMD::from_series(&data, 0.01);
+    let backward: BMD<Lookback<4, 6>, _> = BMD::from_series_in(&data, 0.01, Direction::Backward);
+
+    let start = data.len() / 2 - 15;
+    let (before, rest) = data.split_at(start);
+    let after = &rest[30..];
+
+    let filled =
+        Infill::new(&forward, &backward).fill::<WeightedSpikes<[f32; 6]>, 6>(before, after, 30);
+
+    for frame in before.iter().chain(&filled).chain(after) {
+        println!("{:?}", frame);
+    }
+}
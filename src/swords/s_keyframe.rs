@@ -0,0 +1,42 @@
+use rand::thread_rng;
+
+use blended_markov_distribution::{
+    bmd::{WeightedSpikes, BMD},
+    data,
+    keyframe::{Keyframe, KeyframeSampler},
+    lookback::Lookback,
+};
+
+// start from the recording, pass a pose from the middle of it at frame 90 and end at rest at 150
+pub fn go() {
+    let data = data::sword_6();
+
+    let sword_bmd: BMD<Lookback<4, 6>, _> = BMD::from_series(&data, 0.02);
+
+    let keyframes = [
+        Keyframe {
+            frame: 90,
+            pose: data[data.len() / 2],
+            tolerance: 0.2,
+        },
+        Keyframe {
+            frame: 149,
+            pose: *data.last().unwrap(),
+            tolerance: 0.2,
+        },
+    ];
+
+    let sampler = KeyframeSampler::new(500, 0.2);
+    let best = sampler.best::<_, WeightedSpikes<[f32; 6]>, _, 6>(
+        &sword_bmd,
+        &data[..4],
+        &keyframes,
+        150,
+        &mut thread_rng(),
+    );
+
+    eprintln!("hit: {}, max error: {}", best.hit, best.max_error);
+    for frame in best.frames {
+        println!("{:?}", frame);
+    }
+}
@@ -0,0 +1,19 @@
+use blended_markov_distribution::{
+    bmd::{Interpolate, WeightedSpikes, BMD},
+    data,
+    lookback::{Direction, Lookback},
+};
+
+// works backwards from the last few frames of the swing to make 150 frames that lead into it
+pub fn go() {
+    let data = data::sword_6();
+
+    let backward: BMD<Lookback<4, 6>, _> = BMD::from_series_in(&data, 0.0, Direction::Backward);
+
+    let end = &data[data.len() - 4..];
+    let lead_in = backward.generate_backward::<WeightedSpikes<[f32; 6]>, 6>(end, 150);
+
+    for frame in lead_in.iter().chain(end) {
+        println!("{:?}", frame);
+    }
+}
@@ -0,0 +1,72 @@
+use blended_markov_distribution::{
+    bmd::{Interpolate, WeightedSpikes, BMD},
+    dataset::Dataset,
+    lookback::Lookback,
+    novelty::Novelty,
+    temperature::Schedule,
+};
+
+// the point mass sword rollout replays its clip, see how far each novelty control gets it away from that
+pub fn go() {
+    let dataset = Dataset::sword();
+    let frames = &dataset.clips[0].frames;
+    let bmd: BMD<Lookback<12, 6>, _> = BMD::from_series(frames, 0.0);
+
+    let settings = [
+        ("none", Schedule::Constant(1.0), Novelty::default()),
+        ("temperature 4", Schedule::Constant(4.0), Novelty::default()),
+        (
+            "exclude successor",
+            Schedule::Constant(1.0),
+            Novelty {
+                exclude_successor: true,
+                ..Default::default()
+            },
+        ),
+        (
+            "min ess 3",
+            Schedule::Constant(1.0),
+            Novelty {
+                min_ess: 3.0,
+                ..Default::default()
+            },
+        ),
+    ];
+
+    for (name, schedule, novelty) in settings {
+        let generated = bmd.generate_reweighted::<WeightedSpikes<[f32; 6]>, _, 6>(
+            &frames[..12],
+            120,
+            &mut (schedule, novelty),
+        );
+        let run = dataset.longest_verbatim_run(&generated, 1e-6);
+        eprintln!(
+            "{name}: longest verbatim run {} frames, from training frame {}",
+            run.length, run.source.frame
+        );
+    }
+}
+
+// the same rollout tempered by a schedule: follows the clip to start and end on, freer in the middle
+pub fn anneal() {
+    let dataset = Dataset::sword();
+    let frames = &dataset.clips[0].frames;
+    let bmd: BMD<Lookback<12, 6>, _> = BMD::from_series(frames, 0.0);
+
+    let mut schedule = Schedule::arc(1.0, 4.0);
+    let generated = bmd.generate_reweighted::<WeightedSpikes<[f32; 6]>, _, 6>(
+        &frames[..12],
+        120,
+        &mut schedule,
+    );
+
+    for frame in &generated {
+        println!("{:?}", frame);
+    }
+
+    let run = dataset.longest_verbatim_run(&generated, 1e-6);
+    eprintln!(
+        "longest verbatim run {} frames, starting at generated frame {}",
+        run.length, run.generated_start
+    );
+}
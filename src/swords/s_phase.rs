@@ -0,0 +1,69 @@
+use std::collections::VecDeque;
+
+use blended_markov_distribution::{
+    bmd::{BlendedDist, Interpolate, PositionState, RExp, SpikeDist, WeightedSpikes, BMD},
+    data,
+    lookback::{FromHistory, Lookback},
+};
+
+// a short lookback plus how far through the swing we are, so crossings can be told apart
+#[derive(PositionState)]
+struct Phased {
+    motion: Lookback<4, 6>,
+    #[similarity(weight = 0.5)]
+    phase: RExp,
+}
+
+pub fn go() {
+    let dif_data: Vec<[f32; 6]> = data::sword_6()
+        .windows(2)
+        .map(|win| {
+            let mut buf = [0.0; 6];
+            for i in 0..6 {
+                buf[i] = win[1][i] - win[0][i];
+            }
+            buf
+        })
+        .collect();
+
+    let len = dif_data.len() as f32;
+
+    let sword_bmd = BMD {
+        distributions: dif_data
+            .windows(5)
+            .enumerate()
+            .map(|(i, window)| {
+                (
+                    Phased {
+                        motion: Lookback::from_history(&window[..4]),
+                        phase: RExp(i as f32 / len),
+                    },
+                    SpikeDist {
+                        pos: window[4],
+                        side_len: 0.0,
+                    },
+                )
+            })
+            .collect(),
+    };
+
+    let mut current = data::sword_6()[4];
+
+    let mut last = VecDeque::from_iter(dif_data.iter().take(4).copied());
+
+    for i in 0..150 {
+        let last_buf = last.back().unwrap();
+        for j in 0..6 {
+            current[j] += last_buf[j];
+        }
+        println!("{:?}", current);
+
+        let pos = Phased {
+            motion: Lookback::from_history(last.make_contiguous()),
+            phase: RExp(i as f32 / 150.0),
+        };
+        let new = sword_bmd.interpolate::<WeightedSpikes<[f32; 6]>>(pos).sample();
+        last.pop_front();
+        last.push_back(new);
+    }
+}
@@ -0,0 +1,31 @@
+use std::collections::VecDeque;
+
+use blended_markov_distribution::{
+    bmd::{BlendedDist, Interpolate, WeightedSpikes, BMD},
+    data,
+    features::{Feature, FeatureExtractor, FeatureSet, Features},
+};
+
+// matches on pose + velocity instead of a 72 float lookback, still predicting raw frames
+pub fn go() {
+    let data = data::sword_6();
+
+    let extractor = FeatureSet::sword(
+        vec![(Feature::Pose, 1.0), (Feature::Velocity, 0.05)],
+        1.0 / 24.0,
+    );
+
+    let sword_bmd: BMD<Features, _> = BMD::from_series_with(&extractor, &data, 0.0);
+
+    let span = FeatureExtractor::<6>::span(&extractor);
+    let mut last = VecDeque::from_iter(data.iter().take(span).copied());
+
+    for _ in 0..150 {
+        println!("{:?}", last.back().unwrap());
+
+        let pos = extractor.extract(last.make_contiguous());
+        let new = sword_bmd.interpolate::<WeightedSpikes<[f32; 6]>>(pos).sample();
+        last.pop_front();
+        last.push_back(new);
+    }
+}
@@ -0,0 +1,49 @@
+use std::collections::VecDeque;
+
+use blended_markov_distribution::{
+    bmd::{BlendedDist, WeightedSpikes, BMD},
+    data,
+    lookback::{FromHistory, Lookback},
+    successor::SuccessorSearch,
+};
+
+// the 12 frame sword rollout, but only scoring the successors of last frame's best matches
+pub fn go() {
+    let dif_data: Vec<[f32; 6]> = data::sword_6()
+        .windows(2)
+        .map(|win| {
+            let mut buf = [0.0; 6];
+            for i in 0..6 {
+                buf[i] = win[1][i] - win[0][i];
+            }
+            buf
+        })
+        .collect();
+
+    let sword_bmd: BMD<Lookback<12, 6>, _> = BMD::from_series(&dif_data, 0.0);
+    let mut search = SuccessorSearch::new(8, 4, 0.5);
+
+    let mut current = data::sword_6()[12];
+
+    let mut last = VecDeque::from_iter(dif_data.iter().take(12).copied());
+
+    for _ in 0..150 {
+        let last_buf = last.back().unwrap();
+        for i in 0..6 {
+            current[i] += last_buf[i];
+        }
+        println!("{:?}", current);
+
+        let pos = Lookback::<12, 6>::from_history(last.make_contiguous());
+        let new = search
+            .interpolate::<_, WeightedSpikes<[f32; 6]>>(&sword_bmd, pos)
+            .sample();
+        last.pop_front();
+        last.push_back(new);
+    }
+
+    eprintln!(
+        "{} fast searches, {} full searches",
+        search.fast_searches, search.full_searches
+    );
+}
@@ -0,0 +1,19 @@
+use blended_markov_distribution::{
+    bmd::{Interpolate, WeightedSpikes, BMD},
+    data,
+    lookback::Lookback,
+    weighted::WeightedBMD,
+};
+
+// the sword swing as the hero take, with a rough take of it played backwards weighted well down
+pub fn go() {
+    let hero = data::sword_6();
+    let rough: Vec<[f32; 6]> = hero.iter().rev().copied().collect();
+
+    let mut model = WeightedBMD::uniform(BMD::<Lookback<12, 6>, _>::from_series(&hero, 0.01));
+    model.append(BMD::from_series(&rough, 0.01), 0.1);
+
+    for frame in model.generate::<WeightedSpikes<[f32; 6]>, 6>(&hero[..12], 150) {
+        println!("{:?}", frame);
+    }
+}
@@ -0,0 +1,162 @@
+use crate::bmd::Reweight;
+
+// Raises every weight to 1/temperature, in log space so small weights don't vanish first.
+// Above 1 flattens the weights, below 1 sharpens them, 1 leaves them alone. It works on the
+// weights at generation time, so it is a separate knob from the bandwidth the model was built with.
+pub fn temper(weights: &mut [f32], temperature: f32) {
+    assert!(
+        temperature > 0.0,
+        "temperature has to be above 0, got {temperature}"
+    );
+
+    if temperature == 1.0 {
+        return;
+    }
+
+    let max_log = weights
+        .iter()
+        .filter(|w| **w > 0.0)
+        .map(|w| w.ln())
+        .fold(f32::NEG_INFINITY, f32::max);
+    if max_log == f32::NEG_INFINITY {
+        return;
+    }
+
+    for w in weights.iter_mut().filter(|w| **w > 0.0) {
+        *w = f32::exp((w.ln() - max_log) / temperature);
+    }
+}
+
+// The temperature to use at each step of a rollout, every temperature in it has to be above 0.
+// As a Reweight it tempers each step's weights by its temperature.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Schedule {
+    Constant(f32),
+    // straight from start to end over the rollout
+    Linear { start: f32, end: f32 },
+    // temperature times rate every step, never going below min
+    Exponential { start: f32, rate: f32, min: f32 },
+    // (how far through the rollout from 0 to 1, temperature) sorted by how far through,
+    // linear in between and held flat before the first and after the last
+    Keyframes(Vec<(f32, f32)>),
+}
+
+impl Schedule {
+    // conservative at both ends, more creative in the middle
+    pub fn arc(edges: f32, middle: f32) -> Self {
+        Schedule::Keyframes(vec![(0.0, edges), (0.5, middle), (1.0, edges)])
+    }
+
+    // panics on anything that could give a temperature of 0 or less, or keyframes out of order
+    fn validate(&self) {
+        let valid = match self {
+            Schedule::Constant(temperature) => *temperature > 0.0,
+            Schedule::Linear { start, end } => *start > 0.0 && *end > 0.0,
+            // min keeps it from decaying all the way to 0
+            Schedule::Exponential { start, min, .. } => *start > 0.0 && *min > 0.0,
+            Schedule::Keyframes(keys) => {
+                keys.iter().all(|(_, temperature)| *temperature > 0.0)
+                    && keys.windows(2).all(|pair| pair[0].0 <= pair[1].0)
+            }
+        };
+
+        assert!(valid, "invalid temperature schedule {self:?}");
+    }
+
+    // the temperature for `step` out of `length`
+    pub fn at(&self, step: usize, length: usize) -> f32 {
+        self.validate();
+
+        let t = if length > 1 {
+            step as f32 / (length - 1) as f32
+        } else {
+            0.0
+        };
+
+        match self {
+            Schedule::Constant(temperature) => *temperature,
+            Schedule::Linear { start, end } => start + (end - start) * t,
+            Schedule::Exponential { start, rate, min } => {
+                (start * rate.powi(step as i32)).max(*min)
+            }
+            Schedule::Keyframes(keys) => {
+                let after = keys.iter().position(|(at, _)| *at > t);
+                match after {
+                    None => keys.last().map_or(1.0, |(_, temperature)| *temperature),
+                    Some(0) => keys[0].1,
+                    Some(i) => {
+                        let (a, ta) = keys[i - 1];
+                        let (b, tb) = keys[i];
+                        ta + (tb - ta) * (t - a) / (b - a)
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Reweight for Schedule {
+    fn reweight(&mut self, step: usize, length: usize, weights: &mut [f32]) {
+        temper(weights, self.at(step, length));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn schedules_give_the_temperature_at_each_step() {
+        let linear = Schedule::Linear {
+            start: 1.0,
+            end: 3.0,
+        };
+        assert_eq!(linear.at(0, 5), 1.0);
+        assert_eq!(linear.at(2, 5), 2.0);
+        assert_eq!(linear.at(4, 5), 3.0);
+        // a single step is the start
+        assert_eq!(linear.at(0, 1), 1.0);
+
+        let exponential = Schedule::Exponential {
+            start: 8.0,
+            rate: 0.5,
+            min: 1.5,
+        };
+        assert_eq!(exponential.at(1, 10), 4.0);
+        assert_eq!(exponential.at(3, 10), 1.5);
+
+        let arc = Schedule::arc(1.0, 4.0);
+        assert_eq!(arc.at(0, 5), 1.0);
+        assert_eq!(arc.at(1, 5), 2.5);
+        assert_eq!(arc.at(2, 5), 4.0);
+        assert_eq!(arc.at(4, 5), 1.0);
+
+        // held flat outside the keyframes
+        let keys = Schedule::Keyframes(vec![(0.25, 2.0), (0.75, 4.0)]);
+        assert_eq!(keys.at(0, 5), 2.0);
+        assert_eq!(keys.at(2, 5), 3.0);
+        assert_eq!(keys.at(4, 5), 4.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "temperature has to be above 0")]
+    fn tempering_at_zero_is_rejected() {
+        temper(&mut [0.5, 0.25], 0.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid temperature schedule")]
+    fn schedules_reaching_zero_are_rejected() {
+        Schedule::Linear {
+            start: 1.0,
+            end: 0.0,
+        }
+        .at(0, 10);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid temperature schedule")]
+    fn unsorted_keyframes_are_rejected() {
+        Schedule::Keyframes(vec![(0.5, 1.0), (0.0, 2.0)]).at(0, 10);
+    }
+}
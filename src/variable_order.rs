@@ -0,0 +1,217 @@
+use crate::{
+    bmd::{effective_sample_size, Interpolate},
+    lookback::VariableOrder,
+};
+
+// How the lookback orders are combined for a query
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OrderSelection {
+    // use the longest order that has at least min_ess neighbours, backing off to shorter ones
+    Longest,
+    // blend every order, favouring long orders as long as they are well supported
+    Mix,
+}
+
+// Variable order Blended Markov Distribution, over any model whose states are VariableOrder.
+// Every stored state keeps the full lookback, shorter orders only compare the most recent frames.
+#[derive(Debug)]
+pub struct VariableOrderBMD<M> {
+    pub bmd: M,
+    pub orders: Vec<usize>,
+    pub min_ess: f32,
+    pub selection: OrderSelection,
+}
+
+impl<M> VariableOrderBMD<M>
+where
+    M: Interpolate,
+    M::Pos: VariableOrder,
+{
+    pub fn new(bmd: M, orders: Vec<usize>, min_ess: f32) -> Self {
+        assert!(
+            orders.iter().all(|&o| 0 < o && o <= M::Pos::MAX_ORDER),
+            "orders must be between 1 and the lookback length"
+        );
+
+        VariableOrderBMD {
+            bmd,
+            orders,
+            min_ess,
+            selection: OrderSelection::Longest,
+        }
+    }
+
+    fn weights_per_order(&self, eval_pos: &M::Pos) -> Vec<Vec<f32>> {
+        self.orders
+            .iter()
+            .map(|&order| {
+                self.bmd
+                    .pairs()
+                    .iter()
+                    .enumerate()
+                    .map(|(i, (pos, _))| {
+                        pos.similarity_at_order(eval_pos, order) * self.bmd.pair_weight(i)
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn mix(&self, per_order: &[Vec<f32>]) -> Vec<f32> {
+        // how well supported each order is, 1.0 once it has min_ess effective neighbours
+        let support: Vec<f32> = per_order
+            .iter()
+            .map(|weights| (effective_sample_size(weights) / self.min_ess).min(1.0))
+            .collect();
+
+        let mut mix = vec![0.0; self.orders.len()];
+
+        match self.selection {
+            OrderSelection::Longest => {
+                let best = (0..self.orders.len())
+                    .filter(|&i| support[i] > 0.0)
+                    .max_by(|&i, &j| {
+                        support[i]
+                            .total_cmp(&support[j])
+                            .then(self.orders[i].cmp(&self.orders[j]))
+                    });
+
+                if let Some(i) = best {
+                    mix[i] = 1.0;
+                }
+            }
+            OrderSelection::Mix => {
+                for (i, m) in mix.iter_mut().enumerate() {
+                    *m = support[i] * self.orders[i] as f32;
+                }
+
+                let total: f32 = mix.iter().sum();
+                if total > 0.0 {
+                    mix.iter_mut().for_each(|m| *m /= total);
+                }
+            }
+        }
+
+        mix
+    }
+
+    // how much each entry of `orders` contributes at this position
+    pub fn order_weights(&self, eval_pos: &M::Pos) -> Vec<f32> {
+        self.mix(&self.weights_per_order(eval_pos))
+    }
+}
+
+impl<M> Interpolate for VariableOrderBMD<M>
+where
+    M: Interpolate,
+    M::Pos: VariableOrder,
+{
+    type Pos = M::Pos;
+    type Dist = M::Dist;
+
+    fn pairs(&self) -> &[(M::Pos, M::Dist)] {
+        self.bmd.pairs()
+    }
+
+    fn pair_weight(&self, pair: usize) -> f32 {
+        self.bmd.pair_weight(pair)
+    }
+
+    // every pair's weight depends on all the others through the normalization, so this scores them all
+    fn weight_at(&self, pair: usize, eval_pos: &M::Pos) -> f32 {
+        self.weights(eval_pos).nth(pair).unwrap()
+    }
+
+    // each order's weights are normalized first, so orders only compete through the mix
+    fn weights<'s>(&'s self, eval_pos: &'s M::Pos) -> impl Iterator<Item = f32> + 's {
+        let per_order = self.weights_per_order(eval_pos);
+        let mix = self.mix(&per_order);
+
+        let mut weights = vec![0.0; self.bmd.pairs().len()];
+        for (order_weights, m) in per_order.into_iter().zip(mix).filter(|(_, m)| *m > 0.0) {
+            let total: f32 = order_weights.iter().sum();
+            for (w, ow) in weights.iter_mut().zip(order_weights) {
+                *w += m * ow / total;
+            }
+        }
+
+        weights.into_iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        bmd::{SpikeDist, BMD},
+        lookback::Lookback,
+    };
+
+    // one pair matching the query at both orders, three matching only its newest frame
+    fn model(min_ess: f32) -> VariableOrderBMD<BMD<Lookback<2, 1>, SpikeDist<[f32; 1]>>> {
+        let pair = |older: f32, out: f32| {
+            (
+                Lookback([[older], [0.0]]),
+                SpikeDist {
+                    pos: [out],
+                    side_len: 0.0,
+                },
+            )
+        };
+
+        VariableOrderBMD::new(
+            BMD {
+                distributions: vec![
+                    pair(0.0, 0.0),
+                    pair(5.0, 1.0),
+                    pair(5.0, 2.0),
+                    pair(5.0, 3.0),
+                ],
+            },
+            vec![1, 2],
+            min_ess,
+        )
+    }
+
+    fn query() -> Lookback<2, 1> {
+        Lookback([[0.0], [0.0]])
+    }
+
+    fn assert_close(a: &[f32], b: &[f32]) {
+        assert_eq!(a.len(), b.len());
+        for (x, y) in a.iter().zip(b) {
+            assert!((x - y).abs() < 1e-5, "{a:?} != {b:?}");
+        }
+    }
+
+    #[test]
+    fn longest_takes_the_longest_supported_order() {
+        // both orders have a neighbour, so the tie goes to the longer one
+        assert_eq!(model(1.0).order_weights(&query()), [0.0, 1.0]);
+    }
+
+    #[test]
+    fn longest_backs_off_to_the_best_supported_order() {
+        // only order 1 has 3 effective neighbours, order 2 has just the one
+        assert_eq!(model(3.0).order_weights(&query()), [1.0, 0.0]);
+    }
+
+    #[test]
+    fn mix_is_normalized() {
+        let mut model = model(3.0);
+        model.selection = OrderSelection::Mix;
+
+        // support 1 and 1/3, times the orders 1 and 2
+        assert_close(&model.order_weights(&query()), &[0.6, 0.4]);
+    }
+
+    #[test]
+    fn weights_normalize_each_order_before_mixing() {
+        let mut model = model(3.0);
+        model.selection = OrderSelection::Mix;
+
+        // order 1 spreads its 0.6 over all four, order 2 puts its 0.4 on the exact match
+        let weights: Vec<f32> = model.weights(&query()).collect();
+        assert_close(&weights, &[0.55, 0.15, 0.15, 0.15]);
+    }
+}
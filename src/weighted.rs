@@ -0,0 +1,124 @@
+use std::ops::Range;
+
+use crate::{
+    bmd::{BlendedDist, Interpolate, PositionState, BMD},
+    flat::{FlatIndex, FlatState},
+};
+
+// A BMD where every stored pair also has a weight that multiplies its similarity when blending,
+// e.g. how many training pairs it stands for, or how much a take should count for.
+// A hero take can be weighted up over rough ones, rare moves up over common ones.
+#[derive(Debug)]
+pub struct WeightedBMD<Pos: PositionState, Dist> {
+    pub bmd: BMD<Pos, Dist>,
+    // one per pair, in the same order as the BMD
+    pub weights: Vec<f32>,
+}
+
+impl<Pos: PositionState, Dist> WeightedBMD<Pos, Dist> {
+    // every pair counting once, which blends the same as the plain BMD
+    pub fn uniform(bmd: BMD<Pos, Dist>) -> Self {
+        let weights = vec![1.0; bmd.len()];
+        WeightedBMD { bmd, weights }
+    }
+
+    pub fn push(&mut self, pos: Pos, dist: Dist, weight: f32) {
+        self.bmd.push(pos, dist);
+        self.weights.push(weight);
+    }
+
+    // adds every pair of another BMD, all with the same weight, e.g. a whole clip
+    pub fn append(&mut self, other: BMD<Pos, Dist>, weight: f32) {
+        self.weights
+            .extend(std::iter::repeat_n(weight, other.len()));
+        self.bmd.distributions.extend(other.distributions);
+    }
+
+    // multiplies the weights of a run of pairs, e.g. the ones that came from one clip
+    pub fn scale(&mut self, pairs: Range<usize>, factor: f32) {
+        self.weights[pairs].iter_mut().for_each(|w| *w *= factor);
+    }
+
+    pub fn len(&self) -> usize {
+        self.bmd.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bmd.is_empty()
+    }
+}
+
+impl<Pos: PositionState, Dist> Interpolate for WeightedBMD<Pos, Dist> {
+    type Pos = Pos;
+    type Dist = Dist;
+
+    fn pairs(&self) -> &[(Pos, Dist)] {
+        &self.bmd.distributions
+    }
+
+    fn pair_weight(&self, pair: usize) -> f32 {
+        self.weights[pair]
+    }
+
+    fn weights<'s>(&'s self, eval_pos: &'s Pos) -> impl Iterator<Item = f32> + 's {
+        assert_eq!(self.weights.len(), self.bmd.len(), "one weight per pair");
+
+        self.bmd
+            .distributions
+            .iter()
+            .zip(&self.weights)
+            .map(move |((pos, _), w)| pos.similarity(eval_pos) * w)
+    }
+}
+
+impl<'a, Pos: FlatState, Dist: 'a> WeightedBMD<Pos, Dist> {
+    // same as interpolate, but with similarities from a FlatIndex built from the BMD
+    pub fn interpolate_with<T>(&'a self, index: &FlatIndex, eval_pos: Pos) -> T
+    where
+        T: BlendedDist<'a, &'a Dist> + 'a,
+    {
+        assert_eq!(index.len(), self.bmd.len(), "index is out of date");
+
+        T::from(
+            index
+                .similarities(&eval_pos)
+                .into_iter()
+                .zip(&self.weights)
+                .zip(&self.bmd.distributions)
+                .map(|((similarity, w), (_, dist))| (similarity * w, dist)),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        batch::Batch,
+        bmd::{RExp, SpikeDist, WeightedSpikes},
+    };
+
+    #[test]
+    fn weights_reach_generic_generators() {
+        // the same state predicting 1 or 2, with the 2 weighted out
+        let mut model = WeightedBMD::uniform(BMD {
+            distributions: Vec::new(),
+        });
+        for (out, weight) in [(1.0, 1.0), (2.0, 0.0)] {
+            let spike = SpikeDist {
+                pos: [out],
+                side_len: 0.0,
+            };
+            model.push(RExp(0.0), spike, weight);
+        }
+
+        let rollouts = Batch {
+            rollouts: 8,
+            seed: 3,
+            threads: 2,
+        }
+        .generate::<_, WeightedSpikes<[f32; 1]>, _, _>(&model, &[[0.0]], 5, |_| RExp(0.0));
+
+        assert!(rollouts.iter().flatten().all(|frame| *frame == [1.0]));
+    }
+}